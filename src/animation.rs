@@ -1,666 +1,591 @@
+#[cfg(feature = "debug_animation")]
+mod debug;
+mod events;
+mod foot_ik;
+mod ragdoll;
+mod root_motion;
+
+use std::collections::HashMap;
 use std::time::Duration;
 
-use bevy::{
-    animation::{AnimationTargetId, RepeatAnimation},
-    prelude::*,
-};
-use bevy::animation::AnimationTarget;
-use bevy::color::palettes::css::LIGHT_GRAY;
-use bevy::platform::collections::HashSet;
+use bevy::animation::AnimationTargetId;
+use bevy::app::Animation;
+use bevy::prelude::*;
 // use crate::fx::{EffectHandles, OneShotParticleEffect};
-use crate::game_states::AppState;
+use crate::character_controller::{Character, Grounded};
+use crate::character_definition::{bone_target, CharacterDefinition, CharacterModel, ClipMarkers};
+use crate::combat::AttackKind;
+use crate::game_states::{AppState, InWorld};
+pub use events::*;
+pub use foot_ik::FootIk;
+pub use root_motion::RootMotion;
 
-const FOX_PATH: &str = "models/animated/Fox.glb";
+// How long a state change fades from the old clip to the new one
+const CROSSFADE_DURATION: Duration = Duration::from_millis(250);
 
-pub struct AnimationTestPlugin;
+pub struct CharacterAnimationPlugin;
 
-impl Plugin for AnimationTestPlugin {
+impl Plugin for CharacterAnimationPlugin {
     fn build(&self, app: &mut App) {
         app
-            .init_resource::<FoxFeetTargets>()
-            .init_resource::<FoxAppState>()
             .init_resource::<Animations>()
-            // .add_observer(observe_on_step)
+            .add_event::<Footstep>()
+            .add_event::<AttackActive>()
+            .add_event::<AttackEnd>()
+            .add_event::<RollIFrameEnd>()
+            .add_observer(dispatch_clip_markers)
             .insert_resource(AmbientLight {
                 color: Color::WHITE,
                 brightness: 2000.,
                 affects_lightmapped_meshes: false,
             })
-                .add_systems(OnEnter(AppState::InGame), (setup, setup_ui))
-                .add_systems(Update, (
-                    handle_button_toggles,
-                    update_ui,
-                    setup_animation_graph_once_loaded,
-                    keyboard_animation_control
-                ).run_if(in_state(AppState::InGame)));
+            .add_systems(OnEnter(InWorld), setup)
+            .add_systems(Update, (
+                load_rig_animations.run_if(in_state(InWorld)),
+                build_state_clips,
+                // Scenes spawn while loading, so the graph is ready before the first frame in game
+                attach_locomotion_animator.run_if(in_state(InWorld)),
+                update_locomotion_animation.run_if(in_state(AppState::InGame)),
+                update_upper_body_animation.run_if(in_state(AppState::InGame)),
+                (ragdoll::start_ragdolls, ragdoll::settle_ragdolls, ragdoll::clear_ragdolls)
+                    .before(update_locomotion_animation)
+                    .run_if(in_state(InWorld)),
+                // spawn_step_effects.run_if(in_state(AppState::InGame)),
+            ))
+            // Adjusts the pose the animation just wrote, before it's propagated to the meshes
+            .add_systems(PostUpdate, (
+                foot_ik::find_foot_rigs,
+                root_motion::extract_root_motion,
+                foot_ik::solve_foot_ik,
+            ).chain().after(Animation).before(TransformSystem::TransformPropagate).run_if(in_state(AppState::InGame)))
+            // Dead characters stay limp through the death screen, so this keeps running outside the game
+            .add_systems(PostUpdate, ragdoll::pose_ragdolls
+                .after(Animation)
+                .after(foot_ik::solve_foot_ik)
+                .before(TransformSystem::TransformPropagate)
+                .run_if(in_state(InWorld)));
+
+        #[cfg(feature = "debug_animation")]
+        app.add_plugins(debug::AnimationDebugPlugin);
     }
 }
-// IDs of the mask groups we define for the running fox model.
-//
-// Each mask group defines a set of bones for which animations can be toggled on
-// and off.
-const MASK_GROUP_HEAD: u32 = 0;
-const MASK_GROUP_LEFT_FRONT_LEG: u32 = 1;
-const MASK_GROUP_RIGHT_FRONT_LEG: u32 = 2;
-const MASK_GROUP_LEFT_HIND_LEG: u32 = 3;
-const MASK_GROUP_RIGHT_HIND_LEG: u32 = 4;
-const MASK_GROUP_TAIL: u32 = 5;
-
-// The width in pixels of the small buttons that allow the user to toggle a mask
-// group on or off.
-const MASK_GROUP_BUTTON_WIDTH: f32 = 250.0;
-
-// The names of the bones that each mask group consists of. Each mask group is
-// defined as a (prefix, suffix) tuple. The mask group consists of a single
-// bone chain rooted at the prefix. For example, if the chain's prefix is
-// "A/B/C" and the suffix is "D/E", then the bones that will be included in the
-// mask group are "A/B/C", "A/B/C/D", and "A/B/C/D/E".
-//
-// The fact that our mask groups are single chains of bones isn't an engine
-// requirement; it just so happens to be the case for the model we're using. A
-// mask group can consist of any set of animation targets, regardless of whether
-// they form a single chain.
-const MASK_GROUP_PATHS: [(&str, &str); 6] = [
-    // Head
-    (
-        "root/_rootJoint/b_Root_00/b_Hip_01/b_Spine01_02/b_Spine02_03",
-        "b_Neck_04/b_Head_05",
-    ),
-    // Left front leg
-    (
-        "root/_rootJoint/b_Root_00/b_Hip_01/b_Spine01_02/b_Spine02_03/b_LeftUpperArm_09",
-        "b_LeftForeArm_010/b_LeftHand_011",
-    ),
-    // Right front leg
-    (
-        "root/_rootJoint/b_Root_00/b_Hip_01/b_Spine01_02/b_Spine02_03/b_RightUpperArm_06",
-        "b_RightForeArm_07/b_RightHand_08",
-    ),
-    // Left hind leg
-    (
-        "root/_rootJoint/b_Root_00/b_Hip_01/b_LeftLeg01_015",
-        "b_LeftLeg02_016/b_LeftFoot01_017/b_LeftFoot02_018",
-    ),
-    // Right hind leg
-    (
-        "root/_rootJoint/b_Root_00/b_Hip_01/b_RightLeg01_019",
-        "b_RightLeg02_020/b_RightFoot01_021/b_RightFoot02_022",
-    ),
-    // Tail
-    (
-        "root/_rootJoint/b_Root_00/b_Hip_01/b_Tail01_012",
-        "b_Tail02_013/b_Tail03_014",
-    ),
-];
-
-#[derive(Clone, Copy, Component)]
-struct AnimationControl {
-    // The ID of the mask group that this button controls.
-    group_id: u32,
-    label: AnimationLabel,
-}
 
-#[derive(Clone, Copy, Component, PartialEq, Debug)]
-enum AnimationLabel {
-    Idle = 0,
-    Walk = 1,
-    Run = 2,
-    Off = 3,
+/// The clips of each character definition in the world, loaded with it so the loading screen can wait on them
+#[derive(Resource, Default)]
+pub struct Animations {
+    rigs: HashMap<AssetId<CharacterDefinition>, RigAnimations>,
 }
 
-#[derive(Clone, Debug, Resource)]
-struct AnimationNodes([AnimationNodeIndex; 3]);
-
-#[derive(Clone, Copy, Debug, Resource,Default)]
-struct FoxAppState([MaskGroupState; 6]);
+impl Animations {
+    /// The clips loaded from disk, for the loading screen to wait on
+    pub fn clips(&self) -> impl Iterator<Item = &Handle<AnimationClip>> {
+        self.rigs.values().flat_map(|rig| rig.sources.values())
+    }
 
-#[derive(Clone, Copy, Debug, Default)]
-struct MaskGroupState {
-    clip: u8,
+    fn rig(&self, definition: AssetId<CharacterDefinition>) -> Option<&RigAnimations> {
+        self.rigs.get(&definition)
+    }
 }
 
-
-#[derive(Resource, Default)]
-struct Animations {
-    animations: Vec<AnimationNodeIndex>,
-    graph: Handle<AnimationGraph>,
+/// Clips and bones of one character definition
+struct RigAnimations {
+    sources: HashMap<usize, Handle<AnimationClip>>, // As loaded from the glTF, by clip index
+    source_clips: [usize; 8], // Clip index each state copies, in `LocomotionState::ALL` order
+    states: [Handle<AnimationClip>; 8], // A copy of a source per state, so each carries only its own markers
+    mask_groups: Vec<Vec<AnimationTargetId>>, // Bones of each mask group, in definition order
+    lower_body_mask: AnimationMask, // Groups the upper-body layer leaves to the gait
+    root_motion: Option<AnimationTargetId>, // Bone the roll and attack clips move the character with
+    hips: AnimationTargetId, // Lowered by the foot IK so the legs can reach down
+    legs: Vec<(Foot, [AnimationTargetId; 3])>, // Upper, lower and end bone of each leg
+    markers: ClipMarkers,
 }
 
-#[derive(Event, Reflect, Clone)]
-struct OnStep;
-
-/*
-fn observe_on_step(
-    trigger: Trigger<OnStep>,
-    effects: Res<EffectHandles>,
-    mut commands: Commands,
-    transforms: Query<&GlobalTransform>,
-) {
-    let translation = transforms.get(trigger.entity()).unwrap().translation();
-    commands.spawn((
-        Name::new("step_fire"),
-        OneShotParticleEffect::new(
-            effects.fire_step.clone(),
-            translation.reject_from_normalized(Vec3::Y),
-            1.5
-        ),
-    ));
-
-}
-*/
+impl RigAnimations {
+    fn load(definition: &CharacterDefinition, asset_server: &AssetServer, clips: &Assets<AnimationClip>) -> Self {
+        let source_clips = LocomotionState::ALL.map(|state| definition.clips.get(state));
+        let sources = source_clips
+            .iter()
+            .map(|&index| (index, asset_server.load(GltfAssetLabel::Animation(index).from_asset(definition.model.clone()))))
+            .collect();
+
+        let skeleton = &definition.skeleton;
+        let lower_body_mask = skeleton
+            .mask_groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| !skeleton.upper_body.contains(&group.name))
+            .fold(0, |mask, (index, _)| mask | (1 << index));
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut graphs: ResMut<Assets<AnimationGraph>>,
-) {
+        Self {
+            sources,
+            source_clips,
+            states: std::array::from_fn(|_| clips.reserve_handle()),
+            mask_groups: skeleton.mask_groups.iter().map(|group| group.targets()).collect(),
+            lower_body_mask,
+            root_motion: skeleton.root_motion.as_deref().map(bone_target),
+            hips: bone_target(&definition.skeleton.hips),
+            legs: definition
+                .skeleton
+                .legs
+                .iter()
+                .map(|leg| (leg.foot, [&leg.upper, &leg.lower, &leg.end].map(|path| bone_target(path))))
+                .collect(),
+            markers: definition.markers.clone(),
+        }
+    }
 
-    // Build the animation graph
-    let (graph, node_indices) = AnimationGraph::from_clips([
-        asset_server.load(GltfAssetLabel::Animation(2).from_asset(FOX_PATH)),
-        asset_server.load(GltfAssetLabel::Animation(1).from_asset(FOX_PATH)),
-        asset_server.load(GltfAssetLabel::Animation(0).from_asset(FOX_PATH)),
-    ]);
+    fn source(&self, state: LocomotionState) -> &Handle<AnimationClip> {
+        &self.sources[&self.source_clips[state as usize]]
+    }
 
-    // Insert a resource with the current scene information
-    let graph_handle = graphs.add(graph);
-    commands.insert_resource(Animations {
-        animations: node_indices,
-        graph: graph_handle,
-    });
+    fn state_clip(&self, state: LocomotionState) -> Handle<AnimationClip> {
+        self.states[state as usize].clone()
+    }
 
+    fn foot_target(&self, foot: Foot) -> Option<AnimationTargetId> {
+        self.legs.iter().find(|(rig_foot, _)| *rig_foot == foot).map(|(_, [.., end])| *end)
+    }
 }
 
-fn get_clip<'a>(
-    node: AnimationNodeIndex,
-    graph: &AnimationGraph,
-    clips: &'a mut Assets<AnimationClip>,
-) -> &'a mut AnimationClip {
-    let node = graph.get(node).unwrap();
-    let clip = match &node.node_type {
-        AnimationNodeType::Clip(handle) => clips.get_mut(handle),
-        _ => unreachable!(),
-    };
-    clip.unwrap()
+/// What a character's body is doing, each state playing one clip
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocomotionState {
+    Idle,
+    Walk,
+    Run,
+    Roll,
+    Block,
+    Airborne,
+    LightAttack,
+    HeavyAttack,
 }
 
-fn setup_animation_graph_once_loaded(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut clips: ResMut<Assets<AnimationClip>>,
-    mut animation_graphs: ResMut<Assets<AnimationGraph>>,
-    mut players: Query<(Entity, &mut AnimationPlayer), Added<AnimationPlayer>>,
-    targets: Query<(Entity, &AnimationTarget)>,
-    feet: Res<FoxFeetTargets>,
-) {
-
-    for (entity, mut player) in &mut players {
-        // Load the animation clip from the glTF file.
-        let mut animation_graph = AnimationGraph::new();
-        let blend_node = animation_graph.add_additive_blend(1.0, animation_graph.root);
-
-        let animation_graph_nodes: [AnimationNodeIndex; 3] =
-            std::array::from_fn(|animation_index| {
-                let handle = asset_server.load(
-                    GltfAssetLabel::Animation(animation_index)
-                        .from_asset("models/animated/Fox.glb"),
-                );
-                let mask = if animation_index == 0 { 0 } else { 0x3f };
-                animation_graph.add_clip_with_mask(handle, mask, 1.0, blend_node)
-            });
-
-        // Create each mask group.
-        let mut all_animation_target_ids = HashSet::new();
-        for (mask_group_index, (mask_group_prefix, mask_group_suffix)) in
-            MASK_GROUP_PATHS.iter().enumerate()
+impl LocomotionState {
+    const ALL: [LocomotionState; 8] = [
+        LocomotionState::Idle,
+        LocomotionState::Walk,
+        LocomotionState::Run,
+        LocomotionState::Roll,
+        LocomotionState::Block,
+        LocomotionState::Airborne,
+        LocomotionState::LightAttack,
+        LocomotionState::HeavyAttack,
+    ];
+
+    // States played on the upper body, over whatever the legs are doing
+    const UPPER_BODY: [LocomotionState; 3] = [
+        LocomotionState::Block,
+        LocomotionState::LightAttack,
+        LocomotionState::HeavyAttack,
+    ];
+
+    /// Picks the state the whole body plays from the character's movement. Walk and run are told apart
+    /// by speed, so a walk slowed by blocking or a sprint cut short by stamina show the right gait.
+    pub fn from_character(character: &Character, grounded: bool) -> Self {
+        if character.is_rolling || character.is_recovering {
+            LocomotionState::Roll
+        } else if !grounded {
+            LocomotionState::Airborne
+        } else if !character.is_moving {
+            LocomotionState::Idle
+        } else if character.is_sprinting
+            || character.current_speed >= (character.walk_speed + character.run_speed) * 0.5
         {
-            // Split up the prefix and suffix, and convert them into `Name`s.
-            let prefix: Vec<_> = mask_group_prefix.split('/').map(Name::new).collect();
-            let suffix: Vec<_> = mask_group_suffix.split('/').map(Name::new).collect();
-
-            // Add each bone in the chain to the appropriate mask group.
-            for chain_length in 0..=suffix.len() {
-                let animation_target_id = AnimationTargetId::from_names(
-                    prefix.iter().chain(suffix[0..chain_length].iter()),
-                );
-                animation_graph
-                    .add_target_to_mask_group(animation_target_id, mask_group_index as u32);
-                all_animation_target_ids.insert(animation_target_id);
-            }
+            LocomotionState::Run
+        } else {
+            LocomotionState::Walk
         }
+    }
 
-        // We're doing constructing the animation graph. Add it as an asset.
-        let animation_graph2 = animation_graphs.add(animation_graph.clone());
-        commands
-            .entity(entity)
-            .insert(AnimationGraphHandle(animation_graph2));
-
-        // Remove animation targets that aren't in any of the mask groups. If we
-        // don't do that, those bones will play all animations at once, which is
-        // ugly.
-        for (target_entity, target) in &targets {
-            if !all_animation_target_ids.contains(&target.id) {
-                commands.entity(target_entity).remove::<AnimationTarget>();
+    /// The block or attack played over the upper body, if any. A roll takes over the whole body.
+    pub fn upper_body(character: &Character) -> Option<Self> {
+        if character.is_rolling || character.is_recovering {
+            None
+        } else if character.is_attacking {
+            match character.current_attack {
+                AttackKind::Light => Some(LocomotionState::LightAttack),
+                AttackKind::Heavy => Some(LocomotionState::HeavyAttack),
             }
+        } else if character.is_blocking {
+            Some(LocomotionState::Block)
+        } else {
+            None
+        }
+    }
+
+    /// Markers fired by the state's clip, at seconds into the clip. Walk and run mark the rig's footfalls
+    /// for that gait, attacks mark their hitbox window if the rig has one.
+    fn markers(self, markers: &ClipMarkers) -> Vec<(f32, ClipMarker)> {
+        let footfalls = |footfalls: &[(Foot, f32)]| -> Vec<(f32, ClipMarker)> {
+            footfalls.iter().map(|&(foot, time)| (time, ClipMarker::Footstep(foot))).collect()
+        };
+        let attack = |(active, end): (f32, f32)| vec![
+            (active, ClipMarker::AttackActive),
+            (end, ClipMarker::AttackEnd),
+        ];
+
+        match self {
+            LocomotionState::Walk => footfalls(&markers.walk_footfalls),
+            LocomotionState::Run => footfalls(&markers.run_footfalls),
+            LocomotionState::Roll => vec![(markers.roll_iframes, ClipMarker::RollIFrameEnd)],
+            LocomotionState::LightAttack => markers.attacks.map_or_else(Vec::new, |attacks| attack(attacks.light)),
+            LocomotionState::HeavyAttack => markers.attacks.map_or_else(Vec::new, |attacks| attack(attacks.heavy)),
+            LocomotionState::Idle | LocomotionState::Block | LocomotionState::Airborne => Vec::new(),
         }
+    }
 
-        // Play the animation.
-        for animation_graph_node in animation_graph_nodes {
-            player.play(animation_graph_node).repeat();
+    /// Whether the clip repeats, rolls and attacks play once and restart with each new one
+    fn loops(self) -> bool {
+        !matches!(self, LocomotionState::Roll | LocomotionState::LightAttack | LocomotionState::HeavyAttack)
+    }
 
-            // probably there is a better way than to do this on a loop all the time
-            let anim_clip = get_clip(animation_graph_node, &animation_graph, &mut clips);
-            anim_clip.add_event_to_target(feet.front_left, 0.625, OnStep);
-            anim_clip.add_event_to_target(feet.front_right, 0.5, OnStep);
-            anim_clip.add_event_to_target(feet.back_left, 0.0, OnStep);
-            anim_clip.add_event_to_target(feet.back_right, 0.125, OnStep);
+    /// How far into the roll or attack the character is, to notice a new one starting in the same state
+    fn action_elapsed(self, character: &Character) -> Option<f32> {
+        match self {
+            LocomotionState::Roll => Some(character.roll_elapsed),
+            LocomotionState::LightAttack | LocomotionState::HeavyAttack => Some(character.attack_timer),
+            _ => None,
         }
+    }
 
-        // Record the graph nodes.
-        commands.insert_resource(AnimationNodes(animation_graph_nodes));
+    /// Playback speed of the state's clip. Walk and run follow how fast the character actually moves,
+    /// attacks play at their marked timing. Rolls stretch the clip's `roll_iframes` to the roll's own.
+    fn playback_speed(self, character: &Character, roll_iframes: f32) -> f32 {
+        match self {
+            LocomotionState::Idle | LocomotionState::LightAttack | LocomotionState::HeavyAttack => 1.0,
+            LocomotionState::Walk => character.current_speed / character.walk_speed,
+            LocomotionState::Run => character.current_speed / character.run_speed,
+            LocomotionState::Roll if character.current_roll_iframes > 0.0 => {
+                roll_iframes / character.current_roll_iframes
+            }
+            LocomotionState::Roll => 1.0,
+            LocomotionState::Block => 0.5,
+            LocomotionState::Airborne => 0.3,
+        }
     }
 }
 
-// Adds a button that allows the user to toggle a mask group on and off.
-//
-// The button will automatically become a child of the parent that owns the
-// given `ChildBuilder`.
-fn add_mask_group_control(parent: &mut ChildSpawnerCommands, label: &str, width: Val, mask_group_id: u32) {
-    let button_text_style = (
-        TextFont {
-            font_size: 14.0,
-            ..default()
-        },
-        TextColor::WHITE,
-    );
-    let selected_button_text_style = (button_text_style.0.clone(), TextColor::BLACK);
-    let label_text_style = (
-        button_text_style.0.clone(),
-        TextColor(Color::Srgba(LIGHT_GRAY)),
-    );
-
-    parent
-        .spawn((
-            Node {
-                border: UiRect::all(Val::Px(1.0)),
-                width,
-                flex_direction: FlexDirection::Column,
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
-                padding: UiRect::ZERO,
-                margin: UiRect::ZERO,
-                ..default()
-            },
-            BorderColor(Color::WHITE),
-            BorderRadius::all(Val::Px(3.0)),
-            BackgroundColor(Color::BLACK),
-        ))
-        .with_children(|builder| {
-            builder
-                .spawn((
-                    Node {
-                        border: UiRect::ZERO,
-                        width: Val::Percent(100.0),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
-                        padding: UiRect::ZERO,
-                        margin: UiRect::ZERO,
-                        ..default()
-                    },
-                    BackgroundColor(Color::BLACK),
-                ))
-                .with_child((
-                    Text::new(label),
-                    label_text_style.clone(),
-                    Node {
-                        margin: UiRect::vertical(Val::Px(3.0)),
-                        ..default()
-                    },
-                ));
-
-            builder
-                .spawn((
-                    Node {
-                        width: Val::Percent(100.0),
-                        flex_direction: FlexDirection::Row,
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
-                        border: UiRect::top(Val::Px(1.0)),
-                        ..default()
-                    },
-                    BorderColor(Color::WHITE),
-                ))
-                .with_children(|builder| {
-                    for (index, label) in [
-                        AnimationLabel::Run,
-                        AnimationLabel::Walk,
-                        AnimationLabel::Idle,
-                        AnimationLabel::Off,
-                    ]
-                        .iter()
-                        .enumerate()
-                    {
-                        builder
-                            .spawn((
-                                Button,
-                                BackgroundColor(if index > 0 {
-                                    Color::BLACK
-                                } else {
-                                    Color::WHITE
-                                }),
-                                Node {
-                                    flex_grow: 1.0,
-                                    border: if index > 0 {
-                                        UiRect::left(Val::Px(1.0))
-                                    } else {
-                                        UiRect::ZERO
-                                    },
-                                    ..default()
-                                },
-                                BorderColor(Color::WHITE),
-                                AnimationControl {
-                                    group_id: mask_group_id,
-                                    label: *label,
-                                },
-                            ))
-                            .with_child((
-                                Text(format!("{:?}", label)),
-                                if index > 0 {
-                                    button_text_style.clone()
-                                } else {
-                                    selected_button_text_style.clone()
-                                },
-                                TextLayout::new_with_justify(JustifyText::Center),
-                                Node {
-                                    flex_grow: 1.0,
-                                    margin: UiRect::vertical(Val::Px(3.0)),
-                                    ..default()
-                                },
-                            ));
-                    }
-                });
-        });
+/// Drives an animation player from the [`Character`] whose model it animates
+#[derive(Component)]
+pub struct LocomotionAnimator {
+    pub character: Entity,
+    rig: AssetId<CharacterDefinition>, // Definition of the character's model
+    nodes: [AnimationNodeIndex; 8], // One clip node per state, in `LocomotionState::ALL` order
+    upper_body_layer: AnimationNodeIndex, // Blend node over the block and attack clips
+    state: Option<LocomotionState>, // Whole-body state, None until the first state is played
+    action_elapsed: Option<f32>, // Progress of the roll last frame
+    upper_body: Option<LocomotionState>, // Block or attack playing over the upper body
+    upper_body_weight: f32, // How much of the upper body's pose comes from its layer, from 0 to 1
+    upper_body_elapsed: Option<f32>, // Progress of the attack last frame
+    roll_iframes: f32, // When the roll clip's i-frames end
 }
-fn setup_ui(mut commands: Commands) {
-    // Add help text.
-    commands.spawn((
-        Text::new("Click on a button to toggle animations for its associated bones"),
-        Node {
-            position_type: PositionType::Absolute,
-            left: Val::Px(12.0),
-            top: Val::Px(12.0),
-            ..default()
-        },
-    ));
-
-    // Add the buttons that allow the user to toggle mask groups on and off.
-    commands
-        .spawn(Node {
-            flex_direction: FlexDirection::Column,
-            position_type: PositionType::Absolute,
-            row_gap: Val::Px(6.0),
-            left: Val::Px(12.0),
-            bottom: Val::Px(12.0),
-            ..default()
-        })
-        .with_children(|parent| {
-            let row_node = Node {
-                flex_direction: FlexDirection::Row,
-                column_gap: Val::Px(6.0),
-                ..default()
-            };
 
-            add_mask_group_control(parent, "Head", Val::Auto, MASK_GROUP_HEAD);
-
-            parent.spawn(row_node.clone()).with_children(|parent| {
-                add_mask_group_control(
-                    parent,
-                    "Left Front Leg",
-                    Val::Px(MASK_GROUP_BUTTON_WIDTH),
-                    MASK_GROUP_LEFT_FRONT_LEG,
-                );
-                add_mask_group_control(
-                    parent,
-                    "Right Front Leg",
-                    Val::Px(MASK_GROUP_BUTTON_WIDTH),
-                    MASK_GROUP_RIGHT_FRONT_LEG,
-                );
-            });
-
-            parent.spawn(row_node).with_children(|parent| {
-                add_mask_group_control(
-                    parent,
-                    "Left Hind Leg",
-                    Val::Px(MASK_GROUP_BUTTON_WIDTH),
-                    MASK_GROUP_LEFT_HIND_LEG,
-                );
-                add_mask_group_control(
-                    parent,
-                    "Right Hind Leg",
-                    Val::Px(MASK_GROUP_BUTTON_WIDTH),
-                    MASK_GROUP_RIGHT_HIND_LEG,
-                );
-            });
-
-            add_mask_group_control(parent, "Tail", Val::Auto, MASK_GROUP_TAIL);
-        });
+impl LocomotionAnimator {
+    fn node(&self, state: LocomotionState) -> AnimationNodeIndex {
+        self.nodes[state as usize]
+    }
+
+    /// Forgets what was playing, so the next update starts the clips over
+    fn restart(&mut self) {
+        self.state = None;
+        self.action_elapsed = None;
+        self.upper_body = None;
+        self.upper_body_weight = 0.0;
+        self.upper_body_elapsed = None;
+    }
 }
 
-// A system that handles requests from the user to toggle mask groups on and
-// off.
-fn handle_button_toggles(
-    mut interactions: Query<(&Interaction, &mut AnimationControl), Changed<Interaction>>,
-    mut animation_players: Query<&AnimationGraphHandle, With<AnimationPlayer>>,
-    mut animation_graphs: ResMut<Assets<AnimationGraph>>,
-    mut animation_nodes: Option<ResMut<AnimationNodes>>,
-    mut app_state: ResMut<FoxAppState>,
+/*
+fn spawn_step_effects(
+    mut footsteps: EventReader<Footstep>,
+    effects: Res<EffectHandles>,
+    mut commands: Commands,
 ) {
-    let Some(ref mut animation_nodes) = animation_nodes else {
-        return;
-    };
+    for footstep in footsteps.read() {
+        commands.spawn((
+            Name::new("step_fire"),
+            OneShotParticleEffect::new(
+                effects.fire_step.clone(),
+                footstep.position.reject_from_normalized(Vec3::Y),
+                1.5
+            ),
+        ));
+    }
+}
+*/
 
-    for (interaction, animation_control) in interactions.iter_mut() {
-        // We only care about press events.
-        if *interaction != Interaction::Pressed {
+/// Each world loads the clips of the characters in it
+fn setup(mut animations: ResMut<Animations>) {
+    animations.rigs.clear();
+}
+
+/// Starts loading a character definition's clips once the definition itself has loaded
+fn load_rig_animations(
+    mut animations: ResMut<Animations>,
+    asset_server: Res<AssetServer>,
+    definitions: Res<Assets<CharacterDefinition>>,
+    clips: Res<Assets<AnimationClip>>,
+    models: Query<&CharacterModel>,
+) {
+    for model in &models {
+        let id = model.0.id();
+        if animations.rigs.contains_key(&id) {
             continue;
         }
+        let Some(definition) = definitions.get(id) else {
+            continue;
+        };
+        animations.rigs.insert(id, RigAnimations::load(definition, &asset_server, &clips));
+    }
+}
 
-        // Toggle the state of the clip.
-        app_state.0[animation_control.group_id as usize].clip = animation_control.label as u8;
+/// Fills each state's clip with a copy of its source and the state's markers once the source is loaded.
+/// A reloaded source makes fresh copies, so markers never stack.
+fn build_state_clips(
+    mut asset_events: EventReader<AssetEvent<AnimationClip>>,
+    animations: Res<Animations>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+) {
+    let reloaded: Vec<AssetId<AnimationClip>> = asset_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
 
-        // Now grab the animation player. (There's only one in our case, but we
-        // iterate just for clarity's sake.)
-        for animation_graph_handle in animation_players.iter_mut() {
-            // The animation graph needs to have loaded.
-            let Some(animation_graph) = animation_graphs.get_mut(animation_graph_handle) else {
+    for rig in animations.rigs.values() {
+        for state in LocomotionState::ALL {
+            let handle = &rig.states[state as usize];
+            let source = rig.source(state);
+            if clips.contains(handle) && !reloaded.contains(&source.id()) {
+                continue;
+            }
+            // Checked every frame rather than on load events, a new world's sources may have loaded for the last one
+            let Some(mut clip) = clips.get(source).cloned() else {
                 continue;
             };
 
-            for (clip_index, &animation_node_index) in animation_nodes.0.iter().enumerate() {
-                let Some(animation_node) = animation_graph.get_mut(animation_node_index) else {
-                    continue;
-                };
-
-                if animation_control.label as usize == clip_index {
-                    animation_node.mask &= !(1 << animation_control.group_id);
-                } else {
-                    animation_node.mask |= 1 << animation_control.group_id;
+            for (time, marker) in state.markers(&rig.markers) {
+                // The animation player never reaches a marker past the end of the clip
+                if time > clip.duration() {
+                    warn!("{marker:?} at {time}s is past the end of the {state:?} clip, which is {}s long", clip.duration());
+                }
+                match marker {
+                    // Triggered on the foot bone, so the footstep knows where it landed
+                    ClipMarker::Footstep(foot) => {
+                        if let Some(target) = rig.foot_target(foot) {
+                            clip.add_event_to_target(target, time, marker);
+                        }
+                    }
+                    _ => clip.add_event(time, marker),
                 }
             }
+            clips.insert(handle, clip);
         }
     }
 }
 
-// A system that updates the UI based on the current app state.
-fn update_ui(
-    mut animation_controls: Query<(&AnimationControl, &mut BackgroundColor, &Children)>,
-    texts: Query<Entity, With<Text>>,
-    mut writer: TextUiWriter,
-    app_state: Res<FoxAppState>,
+/// Gives each animation player that belongs to a character's model a graph with a node per state,
+/// the block and attack clips in a layer of their own over the upper body, and hands the character's
+/// hitbox and i-frame timing over to the clips' markers
+fn attach_locomotion_animator(
+    mut commands: Commands,
+    animations: Res<Animations>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    players: Query<Entity, Added<AnimationPlayer>>,
+    parents: Query<&ChildOf>,
+    mut characters: Query<(&mut Character, &CharacterModel)>,
 ) {
-    for (animation_control, mut background_color, kids) in animation_controls.iter_mut() {
-        let enabled =
-            app_state.0[animation_control.group_id as usize].clip == animation_control.label as u8;
-
-        *background_color = if enabled {
-            BackgroundColor(Color::WHITE)
-        } else {
-            BackgroundColor(Color::BLACK)
+    for entity in &players {
+        // The player sits somewhere below the scene root, which is the character itself
+        let Some(character) = parents
+            .iter_ancestors(entity)
+            .find(|ancestor| characters.contains(*ancestor))
+        else {
+            continue;
+        };
+        let Ok((mut character_state, model)) = characters.get_mut(character) else {
+            continue;
+        };
+        // The model is only spawned after its definition, so its clips are known by now
+        let Some(rig) = animations.rig(model.0.id()) else {
+            continue;
         };
+        character_state.animation_timing = true;
+        character_state.marked_attacks = rig.markers.attacks.is_some();
 
-        for &kid in kids {
-            let Ok(text) = texts.get(kid) else {
-                continue;
-            };
+        let mut graph = AnimationGraph::new();
+        for (group, targets) in rig.mask_groups.iter().enumerate() {
+            for &target in targets {
+                graph.add_target_to_mask_group(target, group as u32);
+            }
+        }
 
-            writer.for_each_color(text, |mut color| {
-                color.0 = if enabled { Color::BLACK } else { Color::WHITE };
-            });
+        // Faded in by `update_upper_body_animation`
+        let upper_body_layer = graph.add_blend_with_mask(rig.lower_body_mask, 0.0, graph.root);
+        let nodes = LocomotionState::ALL.map(|state| {
+            let parent = if LocomotionState::UPPER_BODY.contains(&state) { upper_body_layer } else { graph.root };
+            graph.add_clip(rig.state_clip(state), 1.0, parent)
+        });
+
+        commands.entity(entity).insert((
+            AnimationGraphHandle(graphs.add(graph)),
+            AnimationTransitions::new(),
+            FootIk::default(),
+            LocomotionAnimator {
+                character,
+                rig: model.0.id(),
+                nodes,
+                upper_body_layer,
+                state: None,
+                action_elapsed: None,
+                upper_body: None,
+                upper_body_weight: 0.0,
+                upper_body_elapsed: None,
+                roll_iframes: rig.markers.roll_iframes,
+            },
+        ));
+        if rig.root_motion.is_some() {
+            commands.entity(entity).insert(RootMotion::default());
         }
     }
 }
 
-
-fn keyboard_animation_control(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut animation_players: Query<(&mut AnimationPlayer, &mut AnimationTransitions)>,
-    animations: Res<Animations>,
-    mut current_animation: Local<usize>,
+/// Cross-fades each character's whole-body animation to the state its movement is in
+fn update_locomotion_animation(
+    characters: Query<(&Character, Has<Grounded>)>,
+    mut animators: Query<(&mut LocomotionAnimator, &mut AnimationPlayer, &mut AnimationTransitions)>,
 ) {
-    for (mut player, mut transitions) in &mut animation_players {
-        let Some((&playing_animation_index, _)) = player.playing_animations().next() else {
+    for (mut animator, mut player, mut transitions) in &mut animators {
+        let Ok((character, grounded)) = characters.get(animator.character) else {
             continue;
         };
 
-        if keyboard_input.just_pressed(KeyCode::Space) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            if playing_animation.is_paused() {
-                playing_animation.resume();
-            } else {
-                playing_animation.pause();
-            }
-        }
+        let state = LocomotionState::from_character(character, grounded);
+        let node = animator.node(state);
+        let speed = state.playback_speed(character, animator.roll_iframes);
+        let action_elapsed = state.action_elapsed(character);
 
-        if keyboard_input.just_pressed(KeyCode::ArrowUp) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            let speed = playing_animation.speed();
-            playing_animation.set_speed(speed * 1.2);
+        if animator.state != Some(state) {
+            let animation = transitions.play(&mut player, node, CROSSFADE_DURATION).set_speed(speed);
+            if state.loops() {
+                animation.repeat();
+            }
+            animator.state = Some(state);
+        } else if action_elapsed.zip(animator.action_elapsed).is_some_and(|(now, before)| now < before) {
+            // A new roll right after the last one, start its clip over so its markers fire again
+            if let Some(animation) = player.animation_mut(node) {
+                animation.replay();
+            }
+        } else if matches!(state, LocomotionState::Walk | LocomotionState::Run) {
+            // Gaits keep following the speed, which changes with blocking and sprinting
+            if let Some(animation) = player.animation_mut(node) {
+                animation.set_speed(speed);
+            }
         }
+        animator.action_elapsed = action_elapsed;
+    }
+}
 
-        if keyboard_input.just_pressed(KeyCode::ArrowDown) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            let speed = playing_animation.speed();
-            playing_animation.set_speed(speed * 0.8);
-        }
+/// Weight for the upper-body layer's blend node so that `share` of the upper body's pose comes from it.
+/// Sibling nodes are averaged by weight, and the whole-body clips under the root add up to 1.
+fn upper_body_layer_weight(share: f32) -> f32 {
+    share / (1.0 - share).max(0.001)
+}
 
-        if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            let elapsed = playing_animation.seek_time();
-            playing_animation.seek_to(elapsed - 0.1);
-        }
+/// Only writes a changed weight, any write to the graph has the player rebuild it
+fn set_node_weight(graphs: &mut Assets<AnimationGraph>, handle: &AnimationGraphHandle, node: AnimationNodeIndex, weight: f32) {
+    let unchanged = graphs
+        .get(handle)
+        .and_then(|graph| graph.get(node))
+        .is_none_or(|graph_node| graph_node.weight == weight);
+    if unchanged {
+        return;
+    }
+    if let Some(graph_node) = graphs.get_mut(handle).and_then(|graph| graph.get_mut(node)) {
+        graph_node.weight = weight;
+    }
+}
 
-        if keyboard_input.just_pressed(KeyCode::ArrowRight) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            let elapsed = playing_animation.seek_time();
-            playing_animation.seek_to(elapsed + 0.1);
-        }
+/// Fades the block and attack clips in over the upper body while the character blocks or attacks,
+/// and back out when they stop. The legs keep playing the whole-body state underneath.
+fn update_upper_body_animation(
+    time: Res<Time>,
+    characters: Query<&Character>,
+    mut animators: Query<(&mut LocomotionAnimator, &mut AnimationPlayer, &AnimationGraphHandle)>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+) {
+    let step = time.delta_secs() / CROSSFADE_DURATION.as_secs_f32();
 
-        if keyboard_input.just_pressed(KeyCode::Enter) {
-            *current_animation = (*current_animation + 1) % animations.animations.len();
+    for (mut animator, mut player, graph_handle) in &mut animators {
+        let Ok(character) = characters.get(animator.character) else {
+            continue;
+        };
 
-            transitions
-                .play(
-                    &mut player,
-                    animations.animations[*current_animation],
-                    Duration::from_millis(250),
-                )
-                .repeat();
+        let upper_body = LocomotionState::upper_body(character);
+        let elapsed = upper_body.and_then(|state| state.action_elapsed(character));
+        // A new attack right after the last one starts its clip over so its markers fire again
+        let restarted = elapsed.zip(animator.upper_body_elapsed).is_some_and(|(now, before)| now < before);
+
+        let started = upper_body.filter(|_| upper_body != animator.upper_body || restarted);
+        if let Some(state) = started {
+            let node = animator.node(state);
+            let was_playing = player.animation(node).is_some();
+            let layer_showing = animator.upper_body_weight > 0.0;
+            let animation = player.start(node).set_speed(state.playback_speed(character, animator.roll_iframes));
+            if state.loops() {
+                animation.repeat();
+            }
+            // Shows at once while the layer fades in, otherwise cross-fades from the clip already showing
+            if !layer_showing {
+                animation.set_weight(1.0);
+            } else if !was_playing {
+                animation.set_weight(0.0);
+            }
         }
 
-        if keyboard_input.just_pressed(KeyCode::Digit1) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            playing_animation
-                .set_repeat(RepeatAnimation::Count(1))
-                .replay();
-        }
+        animator.upper_body_weight = if upper_body.is_some() {
+            (animator.upper_body_weight + step).min(1.0)
+        } else {
+            (animator.upper_body_weight - step).max(0.0)
+        };
 
-        if keyboard_input.just_pressed(KeyCode::Digit3) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            playing_animation
-                .set_repeat(RepeatAnimation::Count(3))
-                .replay();
+        for state in LocomotionState::UPPER_BODY {
+            let node = animator.node(state);
+            if animator.upper_body_weight == 0.0 {
+                player.stop(node);
+                continue;
+            }
+            // Fading the layer out keeps the last clip as it was
+            let Some(current) = upper_body else {
+                continue;
+            };
+            let Some(animation) = player.animation_mut(node) else {
+                continue;
+            };
+            let weight = if state == current {
+                (animation.weight() + step).min(1.0)
+            } else {
+                (animation.weight() - step).max(0.0)
+            };
+            if weight == 0.0 {
+                player.stop(node);
+            } else {
+                animation.set_weight(weight);
+            }
         }
 
-        if keyboard_input.just_pressed(KeyCode::Digit5) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            playing_animation
-                .set_repeat(RepeatAnimation::Count(5))
-                .replay();
-        }
+        set_node_weight(
+            &mut graphs,
+            graph_handle,
+            animator.upper_body_layer,
+            upper_body_layer_weight(animator.upper_body_weight),
+        );
 
-        if keyboard_input.just_pressed(KeyCode::KeyL) {
-            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
-            playing_animation.set_repeat(RepeatAnimation::Forever);
+        if upper_body.is_some() {
+            animator.upper_body = upper_body;
         }
+        animator.upper_body_elapsed = elapsed;
     }
 }
-
-
-#[derive(Resource)]
-struct FoxFeetTargets {
-    front_right: AnimationTargetId,
-    front_left: AnimationTargetId,
-    back_left: AnimationTargetId,
-    back_right: AnimationTargetId,
-}
-
-impl Default for FoxFeetTargets {
-    fn default() -> Self {
-        // Get the id's of the feet and store them in a resource.
-        let hip_node = ["root", "_rootJoint", "b_Root_00", "b_Hip_01"];
-        let front_left_foot = hip_node.iter().chain(
-            [
-                "b_Spine01_02",
-                "b_Spine02_03",
-                "b_LeftUpperArm_09",
-                "b_LeftForeArm_010",
-                "b_LeftHand_011",
-            ]
-                .iter(),
-        );
-        let front_right_foot = hip_node.iter().chain(
-            [
-                "b_Spine01_02",
-                "b_Spine02_03",
-                "b_RightUpperArm_06",
-                "b_RightForeArm_07",
-                "b_RightHand_08",
-            ]
-                .iter(),
-        );
-        let back_left_foot = hip_node.iter().chain(
-            [
-                "b_LeftLeg01_015",
-                "b_LeftLeg02_016",
-                "b_LeftFoot01_017",
-                "b_LeftFoot02_018",
-            ]
-                .iter(),
-        );
-        let back_right_foot = hip_node.iter().chain(
-            [
-                "b_RightLeg01_019",
-                "b_RightLeg02_020",
-                "b_RightFoot01_021",
-                "b_RightFoot02_022",
-            ]
-                .iter(),
-        );
-        Self {
-            front_left: AnimationTargetId::from_iter(front_left_foot),
-            front_right: AnimationTargetId::from_iter(front_right_foot),
-            back_left: AnimationTargetId::from_iter(back_left_foot),
-            back_right: AnimationTargetId::from_iter(back_right_foot),
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,387 @@
+//! Mask group toggles and playback keys for inspecting a character's clips, adapted from Bevy's mask group example.
+//! Only built with the `debug_animation` feature, Space and the arrow keys collide with gameplay input.
+
+use bevy::animation::RepeatAnimation;
+use bevy::color::palettes::css::LIGHT_GRAY;
+use bevy::prelude::*;
+use crate::character_definition::CharacterDefinition;
+use crate::game_states::{AppState, InWorld};
+use super::{LocomotionAnimator, LocomotionState};
+
+pub(super) struct AnimationDebugPlugin;
+
+impl Plugin for AnimationDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MaskGroupStates>()
+            .add_systems(OnEnter(InWorld), setup_ui)
+            .add_systems(Update, (
+                add_mask_group_controls,
+                handle_button_toggles,
+                update_ui,
+                keyboard_animation_control,
+            ).run_if(in_state(AppState::InGame)));
+    }
+}
+
+// The width in pixels of the small buttons that allow the user to toggle a mask
+// group on or off.
+const MASK_GROUP_BUTTON_WIDTH: f32 = 250.0;
+
+#[derive(Clone, Copy, Component)]
+struct AnimationControl {
+    // The ID of the mask group that this button controls.
+    group_id: u32,
+    label: AnimationLabel,
+}
+
+#[derive(Clone, Copy, Component, PartialEq, Debug)]
+enum AnimationLabel {
+    Idle = 0,
+    Walk = 1,
+    Run = 2,
+    Off = 3,
+}
+
+/// Which clip each of the character definition's mask groups plays, in definition order
+#[derive(Clone, Debug, Resource, Default)]
+struct MaskGroupStates(Vec<MaskGroupState>);
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MaskGroupState {
+    clip: u8,
+}
+
+// The gait nodes the mask group buttons switch between, in `AnimationLabel` order
+const GAITS: [LocomotionState; 3] = [LocomotionState::Idle, LocomotionState::Walk, LocomotionState::Run];
+
+/// Column the mask group buttons are added to once a character's definition is known
+#[derive(Component)]
+struct MaskGroupControls;
+
+/// Adds a button for each of the character definition's mask groups, which the animator's graph
+/// already has, the first time an animator appears
+fn add_mask_group_controls(
+    mut commands: Commands,
+    animators: Query<&LocomotionAnimator, Added<LocomotionAnimator>>,
+    definitions: Res<Assets<CharacterDefinition>>,
+    controls: Query<Entity, With<MaskGroupControls>>,
+    mut states: ResMut<MaskGroupStates>,
+) {
+    for animator in &animators {
+        if !states.0.is_empty() {
+            continue;
+        }
+        let Some(definition) = definitions.get(animator.rig) else {
+            continue;
+        };
+        let mask_groups = &definition.skeleton.mask_groups;
+
+        states.0 = vec![MaskGroupState::default(); mask_groups.len()];
+        for entity in &controls {
+            commands.entity(entity).with_children(|parent| {
+                for (mask_group_index, mask_group) in mask_groups.iter().enumerate() {
+                    add_mask_group_control(
+                        parent,
+                        &mask_group.name,
+                        Val::Px(MASK_GROUP_BUTTON_WIDTH),
+                        mask_group_index as u32,
+                    );
+                }
+            });
+        }
+    }
+}
+
+// Adds a button that allows the user to toggle a mask group on and off.
+//
+// The button will automatically become a child of the parent that owns the
+// given `ChildBuilder`.
+fn add_mask_group_control(parent: &mut ChildSpawnerCommands, label: &str, width: Val, mask_group_id: u32) {
+    let button_text_style = (
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor::WHITE,
+    );
+    let selected_button_text_style = (button_text_style.0.clone(), TextColor::BLACK);
+    let label_text_style = (
+        button_text_style.0.clone(),
+        TextColor(Color::Srgba(LIGHT_GRAY)),
+    );
+
+    parent
+        .spawn((
+            Node {
+                border: UiRect::all(Val::Px(1.0)),
+                width,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                padding: UiRect::ZERO,
+                margin: UiRect::ZERO,
+                ..default()
+            },
+            BorderColor(Color::WHITE),
+            BorderRadius::all(Val::Px(3.0)),
+            BackgroundColor(Color::BLACK),
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn((
+                    Node {
+                        border: UiRect::ZERO,
+                        width: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::ZERO,
+                        margin: UiRect::ZERO,
+                        ..default()
+                    },
+                    BackgroundColor(Color::BLACK),
+                ))
+                .with_child((
+                    Text::new(label),
+                    label_text_style.clone(),
+                    Node {
+                        margin: UiRect::vertical(Val::Px(3.0)),
+                        ..default()
+                    },
+                ));
+
+            builder
+                .spawn((
+                    Node {
+                        width: Val::Percent(100.0),
+                        flex_direction: FlexDirection::Row,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        border: UiRect::top(Val::Px(1.0)),
+                        ..default()
+                    },
+                    BorderColor(Color::WHITE),
+                ))
+                .with_children(|builder| {
+                    for (index, label) in [
+                        AnimationLabel::Run,
+                        AnimationLabel::Walk,
+                        AnimationLabel::Idle,
+                        AnimationLabel::Off,
+                    ]
+                        .iter()
+                        .enumerate()
+                    {
+                        builder
+                            .spawn((
+                                Button,
+                                BackgroundColor(if index > 0 {
+                                    Color::BLACK
+                                } else {
+                                    Color::WHITE
+                                }),
+                                Node {
+                                    flex_grow: 1.0,
+                                    border: if index > 0 {
+                                        UiRect::left(Val::Px(1.0))
+                                    } else {
+                                        UiRect::ZERO
+                                    },
+                                    ..default()
+                                },
+                                BorderColor(Color::WHITE),
+                                AnimationControl {
+                                    group_id: mask_group_id,
+                                    label: *label,
+                                },
+                            ))
+                            .with_child((
+                                Text(format!("{:?}", label)),
+                                if index > 0 {
+                                    button_text_style.clone()
+                                } else {
+                                    selected_button_text_style.clone()
+                                },
+                                TextLayout::new_with_justify(JustifyText::Center),
+                                Node {
+                                    flex_grow: 1.0,
+                                    margin: UiRect::vertical(Val::Px(3.0)),
+                                    ..default()
+                                },
+                            ));
+                    }
+                });
+        });
+}
+fn setup_ui(mut commands: Commands, mut states: ResMut<MaskGroupStates>) {
+    // Add help text.
+    commands.spawn((
+        Text::new("Click on a button to toggle animations for its associated bones"),
+        StateScoped(InWorld),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(12.0),
+            top: Val::Px(12.0),
+            ..default()
+        },
+    ));
+
+    // The buttons that allow the user to toggle mask groups on and off go here once the
+    // character's definition is known.
+    states.0.clear();
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            position_type: PositionType::Absolute,
+            row_gap: Val::Px(6.0),
+            left: Val::Px(12.0),
+            bottom: Val::Px(12.0),
+            ..default()
+        },
+        MaskGroupControls,
+        StateScoped(InWorld),
+    ));
+}
+
+// A system that handles requests from the user to toggle mask groups on and
+// off.
+fn handle_button_toggles(
+    mut interactions: Query<(&Interaction, &mut AnimationControl), Changed<Interaction>>,
+    animators: Query<(&LocomotionAnimator, &AnimationGraphHandle)>,
+    mut animation_graphs: ResMut<Assets<AnimationGraph>>,
+    mut states: ResMut<MaskGroupStates>,
+) {
+    for (interaction, animation_control) in interactions.iter_mut() {
+        // We only care about press events.
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        // Toggle the state of the clip.
+        states.0[animation_control.group_id as usize].clip = animation_control.label as u8;
+
+        for (animator, animation_graph_handle) in &animators {
+            // The animation graph needs to have loaded.
+            let Some(animation_graph) = animation_graphs.get_mut(animation_graph_handle) else {
+                continue;
+            };
+
+            for (clip_index, gait) in GAITS.into_iter().enumerate() {
+                let Some(animation_node) = animation_graph.get_mut(animator.node(gait)) else {
+                    continue;
+                };
+
+                if animation_control.label as usize == clip_index {
+                    animation_node.mask &= !(1 << animation_control.group_id);
+                } else {
+                    animation_node.mask |= 1 << animation_control.group_id;
+                }
+            }
+        }
+    }
+}
+
+// A system that updates the UI based on the current app state.
+fn update_ui(
+    mut animation_controls: Query<(&AnimationControl, &mut BackgroundColor, &Children)>,
+    texts: Query<Entity, With<Text>>,
+    mut writer: TextUiWriter,
+    states: Res<MaskGroupStates>,
+) {
+    for (animation_control, mut background_color, kids) in animation_controls.iter_mut() {
+        let enabled =
+            states.0[animation_control.group_id as usize].clip == animation_control.label as u8;
+
+        *background_color = if enabled {
+            BackgroundColor(Color::WHITE)
+        } else {
+            BackgroundColor(Color::BLACK)
+        };
+
+        for &kid in kids {
+            let Ok(text) = texts.get(kid) else {
+                continue;
+            };
+
+            writer.for_each_color(text, |mut color| {
+                color.0 = if enabled { Color::BLACK } else { Color::WHITE };
+            });
+        }
+    }
+}
+
+/// Pauses, scrubs, speeds up or repeats the clip the animator is playing. Walking and running
+/// set their own speed every frame, so the speed keys only stick for the other states.
+fn keyboard_animation_control(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut animation_players: Query<(&LocomotionAnimator, &mut AnimationPlayer)>,
+) {
+    for (animator, mut player) in &mut animation_players {
+        let Some(state) = animator.state else {
+            continue;
+        };
+        let playing_animation_index = animator.node(state);
+        if !player.is_playing_animation(playing_animation_index) {
+            continue;
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Space) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            if playing_animation.is_paused() {
+                playing_animation.resume();
+            } else {
+                playing_animation.pause();
+            }
+        }
+
+        if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            let speed = playing_animation.speed();
+            playing_animation.set_speed(speed * 1.2);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            let speed = playing_animation.speed();
+            playing_animation.set_speed(speed * 0.8);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            let elapsed = playing_animation.seek_time();
+            playing_animation.seek_to(elapsed - 0.1);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            let elapsed = playing_animation.seek_time();
+            playing_animation.seek_to(elapsed + 0.1);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Digit1) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            playing_animation
+                .set_repeat(RepeatAnimation::Count(1))
+                .replay();
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Digit3) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            playing_animation
+                .set_repeat(RepeatAnimation::Count(3))
+                .replay();
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Digit5) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            playing_animation
+                .set_repeat(RepeatAnimation::Count(5))
+                .replay();
+        }
+
+        if keyboard_input.just_pressed(KeyCode::KeyL) {
+            let playing_animation = player.animation_mut(playing_animation_index).unwrap();
+            playing_animation.set_repeat(RepeatAnimation::Forever);
+        }
+    }
+}
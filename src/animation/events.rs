@@ -0,0 +1,98 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::character_controller::Character;
+
+/// One of a quadruped's feet
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Foot {
+    FrontLeft,
+    FrontRight,
+    BackLeft,
+    BackRight,
+}
+
+/// Placed on a clip at the time something happens in it. The animation player triggers it on its own
+/// entity, or on the foot bone for footsteps, and [`dispatch_clip_markers`] passes it on as one of the
+/// typed events below for the character the model belongs to.
+#[derive(Event, Reflect, Debug, Clone, Copy, PartialEq)]
+pub enum ClipMarker {
+    Footstep(Foot),
+    AttackActive,
+    AttackEnd,
+    RollIFrameEnd, // I-frames open as the roll starts, only their end is marked
+}
+
+/// A foot touched down, at its bone's position in the world
+#[derive(Event, Reflect, Debug, Clone, Copy)]
+pub struct Footstep {
+    pub character: Entity,
+    pub foot: Foot,
+    pub position: Vec3,
+}
+
+/// The attack's hitbox should come out
+#[derive(Event, Reflect, Debug, Clone, Copy)]
+pub struct AttackActive {
+    pub character: Entity,
+}
+
+/// The attack's hitbox should go away, the rest of the swing is recovery
+#[derive(Event, Reflect, Debug, Clone, Copy)]
+pub struct AttackEnd {
+    pub character: Entity,
+}
+
+/// The roll can be hit again
+#[derive(Event, Reflect, Debug, Clone, Copy)]
+pub struct RollIFrameEnd {
+    pub character: Entity,
+}
+
+/// Writers for every typed event a marker can turn into
+#[derive(SystemParam)]
+pub(super) struct MarkerWriters<'w> {
+    footsteps: EventWriter<'w, Footstep>,
+    attack_active: EventWriter<'w, AttackActive>,
+    attack_end: EventWriter<'w, AttackEnd>,
+    iframe_end: EventWriter<'w, RollIFrameEnd>,
+}
+
+/// Sends the typed event for a marker reached by a character's clip
+pub(super) fn dispatch_clip_markers(
+    trigger: Trigger<ClipMarker>,
+    parents: Query<&ChildOf>,
+    characters: Query<(), With<Character>>,
+    transforms: Query<&GlobalTransform>,
+    mut writers: MarkerWriters,
+) {
+    let target = trigger.target();
+    let Some(character) = parents
+        .iter_ancestors(target)
+        .find(|ancestor| characters.contains(*ancestor))
+    else {
+        return;
+    };
+
+    match *trigger.event() {
+        ClipMarker::Footstep(foot) => {
+            let Ok(transform) = transforms.get(target) else {
+                return;
+            };
+            writers.footsteps.write(Footstep {
+                character,
+                foot,
+                position: transform.translation(),
+            });
+        }
+        ClipMarker::AttackActive => {
+            writers.attack_active.write(AttackActive { character });
+        }
+        ClipMarker::AttackEnd => {
+            writers.attack_end.write(AttackEnd { character });
+        }
+        ClipMarker::RollIFrameEnd => {
+            writers.iframe_end.write(RollIFrameEnd { character });
+        }
+    }
+}
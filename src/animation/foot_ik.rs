@@ -0,0 +1,245 @@
+use avian3d::prelude::{SpatialQuery, SpatialQueryFilter};
+use bevy::animation::{AnimationTarget, AnimationTargetId};
+use bevy::ecs::query::QueryFilter;
+use bevy::prelude::*;
+use crate::character_controller::{Character, Grounded};
+use super::{Animations, LocomotionAnimator, RigAnimations};
+
+// How far above an animated foot the ground ray starts, so feet sunk into a step still find its top
+const FOOT_RAY_HEIGHT: f32 = 0.3;
+// Furthest a foot is moved up or down to meet the ground, in world units
+const MAX_FOOT_OFFSET: f32 = 0.25;
+// Rays from the body look this far below it for the ground the animation assumes
+const BODY_RAY_LENGTH: f32 = 2.0;
+// How quickly the IK fades in after landing and out after leaving the ground, per second
+const IK_BLEND_SPEED: f32 = 6.0;
+// How quickly the hips follow the lowest foot
+const HIP_SMOOTHING: f32 = 12.0;
+
+/// Plants a character model's feet on the ground under them and lowers its hips to match
+#[derive(Component, Default)]
+pub struct FootIk {
+    rig: Option<FootRig>, // Found once the skeleton has spawned
+    weight: f32,          // 0 leaves the animated pose alone, 1 applies the full correction
+    hip_drop: f32,        // Smoothed distance the hips are lowered, in world units
+    hip_written: Option<(Vec3, Vec3)>, // Hip translation set last frame, and the offset included in it
+}
+
+struct FootRig {
+    hips: Entity,
+    legs: Vec<Leg>,
+}
+
+/// A two-bone chain ending in a foot, the chain named by the character definition
+struct Leg {
+    upper: Entity,
+    lower: Entity,
+    foot: Entity,
+}
+
+impl FootRig {
+    /// Looks up the hip and leg bones animated by `player`, if they have all spawned
+    fn find(
+        player: Entity,
+        animations: &RigAnimations,
+        targets: &Query<(Entity, &AnimationTarget)>,
+    ) -> Option<Self> {
+        let bone = |id: AnimationTargetId| {
+            targets
+                .iter()
+                .find(|(_, target)| target.player == player && target.id == id)
+                .map(|(entity, _)| entity)
+        };
+
+        let mut legs = Vec::new();
+        for &(_, [upper, lower, foot]) in &animations.legs {
+            legs.push(Leg {
+                upper: bone(upper)?,
+                lower: bone(lower)?,
+                foot: bone(foot)?,
+            });
+        }
+
+        Some(Self {
+            hips: bone(animations.hips)?,
+            legs,
+        })
+    }
+}
+
+/// World transform of an entity from the local transforms above it. The animation has just written
+/// new local transforms, and they won't be propagated until after this pass.
+pub(super) fn world_transform<F: QueryFilter>(
+    entity: Entity,
+    transforms: &Query<&mut Transform, F>,
+    parents: &Query<&ChildOf>,
+) -> GlobalTransform {
+    let chain: Vec<Entity> = std::iter::once(entity).chain(parents.iter_ancestors(entity)).collect();
+    chain.iter().rev().fold(GlobalTransform::IDENTITY, |world, &entity| match transforms.get(entity) {
+        Ok(local) => world.mul_transform(*local),
+        Err(_) => world,
+    })
+}
+
+/// Rotations to apply to the upper and lower bone, in their local space, so the end of a two-bone chain
+/// reaches `target`. The knee bends in the plane the chain is already bent in until the end is as far
+/// from the hip as the target, then the whole chain swings from where the end now is toward the target.
+fn solve_two_bone(a: &GlobalTransform, b: &GlobalTransform, c: Vec3, target: Vec3) -> (Quat, Quat) {
+    let (a_pos, b_pos) = (a.translation(), b.translation());
+    let (a_rot, b_rot) = (a.rotation(), b.rotation());
+
+    let upper_length = (b_pos - a_pos).length();
+    let lower_length = (c - b_pos).length();
+    let target_length = (target - a_pos)
+        .length()
+        .clamp(0.01, upper_length + lower_length - 0.01);
+
+    // Current angle at the knee, and what it needs to be to reach the target's distance
+    let knee_angle = (a_pos - b_pos).normalize_or_zero().dot((c - b_pos).normalize_or_zero()).clamp(-1.0, 1.0).acos();
+    let knee_angle_target = ((target_length * target_length - upper_length * upper_length - lower_length * lower_length)
+        / (-2.0 * upper_length * lower_length))
+        .clamp(-1.0, 1.0)
+        .acos();
+
+    // Turning the lower bone about this axis opens the knee. Fully straight, there's no plane to bend in.
+    let bend_axis = (a_pos - b_pos).cross(c - b_pos).normalize_or_zero();
+    let bend = if bend_axis == Vec3::ZERO {
+        Quat::IDENTITY
+    } else {
+        Quat::from_axis_angle(bend_axis, knee_angle_target - knee_angle)
+    };
+    let bent_end = b_pos + bend * (c - b_pos);
+
+    let from = (bent_end - a_pos).normalize_or_zero();
+    let to = (target - a_pos).normalize_or_zero();
+    let swing = if from == Vec3::ZERO || to == Vec3::ZERO { Quat::IDENTITY } else { Quat::from_rotation_arc(from, to) };
+
+    // Both were worked out in world space, the upper bone's swing carries the lower bone along with it
+    let upper = a_rot.inverse() * swing * a_rot;
+    let lower = b_rot.inverse() * bend * b_rot;
+    (upper, lower)
+}
+
+/// Height of the first surface below `origin`, ignoring the character itself
+fn ground_height(spatial_query: &SpatialQuery, origin: Vec3, length: f32, filter: &SpatialQueryFilter) -> Option<f32> {
+    spatial_query
+        .cast_ray(origin, Dir3::NEG_Y, length, true, filter)
+        .map(|hit| origin.y - hit.distance)
+}
+
+/// Finds the leg bones of skeletons that spawned since last frame
+pub(super) fn find_foot_rigs(
+    animations: Res<Animations>,
+    mut animators: Query<(Entity, &LocomotionAnimator, &mut FootIk)>,
+    targets: Query<(Entity, &AnimationTarget)>,
+) {
+    for (player, animator, mut ik) in &mut animators {
+        if ik.rig.is_some() {
+            continue;
+        }
+        if let Some(rig) = animations.rig(animator.rig) {
+            ik.rig = FootRig::find(player, rig, &targets);
+        }
+    }
+}
+
+/// Runs after the animation has posed the skeleton. Each foot is moved by how much higher or lower
+/// the ground under it is than the ground under the body, and the hips drop so the lowest foot can reach.
+pub(super) fn solve_foot_ik(
+    time: Res<Time>,
+    spatial_query: SpatialQuery,
+    mut animators: Query<(&LocomotionAnimator, &mut FootIk)>,
+    characters: Query<Has<Grounded>, With<Character>>,
+    parents: Query<&ChildOf>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let delta = time.delta_secs();
+
+    for (animator, mut ik) in &mut animators {
+        let Ok(grounded) = characters.get(animator.character) else {
+            continue;
+        };
+
+        let step = IK_BLEND_SPEED * delta;
+        ik.weight = if grounded { (ik.weight + step).min(1.0) } else { (ik.weight - step).max(0.0) };
+        let weight = ik.weight;
+        let Some(rig) = &ik.rig else {
+            continue;
+        };
+
+        let filter = SpatialQueryFilter::default().with_excluded_entities([animator.character]);
+        let body = world_transform(animator.character, &transforms, &parents).translation();
+        let body_ground = ground_height(&spatial_query, body, BODY_RAY_LENGTH, &filter);
+
+        // Where each foot should go, from the pose the animation left it in. Still worked out with the
+        // IK faded out, so the hips ease back up instead of snapping.
+        let foot_targets: Vec<(Vec3, f32)> = rig.legs.iter().map(|leg| {
+            let foot = world_transform(leg.foot, &transforms, &parents).translation();
+            let origin = foot + Vec3::Y * FOOT_RAY_HEIGHT;
+            let ground = body_ground
+                .zip(ground_height(&spatial_query, origin, FOOT_RAY_HEIGHT + MAX_FOOT_OFFSET, &filter));
+            let offset = ground
+                .map_or(0.0, |(body_ground, ground)| (ground - body_ground).clamp(-MAX_FOOT_OFFSET, MAX_FOOT_OFFSET))
+                * weight;
+            (foot, offset)
+        }).collect();
+
+        let lowest = foot_targets.iter().map(|(_, offset)| *offset).fold(0.0, f32::min);
+        let hip_drop = ik.hip_drop + (lowest - ik.hip_drop) * (HIP_SMOOTHING * delta).min(1.0);
+
+        let mut hip_written = None;
+        let hips_parent = parents.get(rig.hips).map(|child_of| child_of.parent());
+        let hip_offset = hips_parent
+            .map(|parent| world_transform(parent, &transforms, &parents).affine().inverse().transform_vector3(Vec3::Y * hip_drop))
+            .unwrap_or(Vec3::ZERO);
+        if let Ok(mut hips) = transforms.get_mut(rig.hips) {
+            // Clips that don't animate the hip translation leave last frame's offset in place
+            let animated = match ik.hip_written {
+                Some((written, offset)) if hips.translation == written => written - offset,
+                _ => hips.translation,
+            };
+            hips.translation = animated + hip_offset;
+            hip_written = Some((hips.translation, hip_offset));
+        }
+
+        for (leg, (foot, offset)) in rig.legs.iter().zip(foot_targets) {
+            let target = foot + Vec3::Y * offset;
+            let upper = world_transform(leg.upper, &transforms, &parents);
+            let lower = world_transform(leg.lower, &transforms, &parents);
+            let end = world_transform(leg.foot, &transforms, &parents).translation();
+            let (upper_correction, lower_correction) = solve_two_bone(&upper, &lower, end, target);
+
+            if let Ok(mut transform) = transforms.get_mut(leg.upper) {
+                transform.rotation *= upper_correction;
+            }
+            if let Ok(mut transform) = transforms.get_mut(leg.lower) {
+                transform.rotation *= lower_correction;
+            }
+        }
+
+        ik.hip_drop = hip_drop;
+        ik.hip_written = hip_written;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_bone_chain_reaches_target() {
+        let (a, b, c) = (Vec3::ZERO, Vec3::new(0.0, -1.0, 0.3), Vec3::new(0.0, -2.0, 0.0));
+        let target = Vec3::new(0.2, -1.6, 0.1);
+        let (upper, lower) = solve_two_bone(
+            &GlobalTransform::from_translation(a),
+            &GlobalTransform::from_translation(b),
+            c,
+            target,
+        );
+
+        // The lower bone hangs off the upper one, so it turns with it
+        let bent_b = a + upper * (b - a);
+        let end = bent_b + upper * lower * (c - b);
+        assert!(end.distance(target) < 1e-4, "end {end} missed {target}");
+    }
+}
@@ -0,0 +1,331 @@
+use avian3d::prelude::*;
+use bevy::animation::{AnimationTarget, AnimationTargetId};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use crate::character_controller::{Character, GroundNormal};
+use crate::combat::{DeathEvent, Health};
+use crate::game_states::InWorld;
+use crate::physics::GameLayer;
+use super::foot_ik::world_transform;
+use super::{Animations, LocomotionAnimator};
+
+// Capsule radius of a ragdoll part, as a fraction of its bone's length
+const PART_RADIUS: f32 = 0.25;
+// Bones shorter than this carry no part and follow the bone above them
+const MIN_PART_LENGTH: f32 = 0.001;
+// How far a joint inside a chain bends either way from the pose at death, in radians
+const HINGE_LIMIT: f32 = 1.2;
+// How far the start of a chain swings and twists on the bone it hangs off, in radians
+const SWING_LIMIT: f32 = 0.8;
+const TWIST_LIMIT: f32 = 0.4;
+// Damping on the parts, so a body doesn't keep flopping once it's down
+const LINEAR_DAMPING: f32 = 0.2;
+const ANGULAR_DAMPING: f32 = 2.0;
+// Parts moving slower than this, in units or radians per second, count as at rest
+const SETTLE_SPEED: f32 = 0.05;
+// How long every part has to be at rest before the ragdoll is frozen in place
+const SETTLE_TIME: f32 = 1.0;
+
+/// A character model gone limp on death. Each segment of the rig's mask group chains is carried by
+/// a capsule body, jointed to the body of the bone it hangs off, and the bones follow the bodies.
+#[derive(Component)]
+pub struct Ragdoll {
+    parts: Vec<RagdollPart>, // Parents before children, so each bone is posed after the one it hangs off
+    joints: Vec<Entity>,
+    settled_for: f32, // How long every part has been at rest
+    frozen: bool,     // Settled for long enough, the bodies no longer move
+}
+
+struct RagdollPart {
+    bone: Entity,
+    body: Entity,
+    rest: Transform, // The bone's local transform at death, put back when the character gets up
+    rotation: Quat,  // The bone's world rotation at death. Bodies start unrotated, so joint limits are measured from this pose.
+    scale: Vec3,     // The bone's world scale
+    places_bone: bool, // Parts with no part above them move their bone as well as turning it
+}
+
+/// Stands in for a ragdoll on a character whose model has no rig. The character's own body is let
+/// fall over, keeping the speed it died with, and frozen once it comes to rest.
+#[derive(Component, Default)]
+pub struct BodyRagdoll {
+    settled_for: f32,
+    frozen: bool,
+}
+
+/// Physics body standing in for a ragdoll bone
+#[derive(Component)]
+struct RagdollBody;
+
+/// The posed skeleton of a model, as it was last propagated
+#[derive(SystemParam)]
+pub(super) struct Skeleton<'w, 's> {
+    targets: Query<'w, 's, (Entity, &'static AnimationTarget)>,
+    parents: Query<'w, 's, &'static ChildOf>,
+    transforms: Query<'w, 's, (&'static Transform, &'static GlobalTransform)>,
+}
+
+impl Skeleton<'_, '_> {
+    /// The bones of a chain animated by `player`, or None if any of them hasn't spawned
+    fn chain(&self, player: Entity, ids: &[AnimationTargetId]) -> Option<Vec<Entity>> {
+        ids.iter()
+            .map(|&id| {
+                self.targets
+                    .iter()
+                    .find(|(_, target)| target.player == player && target.id == id)
+                    .map(|(entity, _)| entity)
+            })
+            .collect()
+    }
+
+    fn position(&self, bone: Entity) -> Option<Vec3> {
+        self.transforms.get(bone).ok().map(|(_, global)| global.translation())
+    }
+}
+
+/// Turns the model of a character that just died into a ragdoll, starting from the pose and
+/// speed it died with. The character's own body is switched off while the ragdoll is down.
+/// Characters without a rigged model fall over as a single [`BodyRagdoll`] instead.
+pub(super) fn start_ragdolls(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    animations: Res<Animations>,
+    mut animators: Query<(Entity, &LocomotionAnimator, &mut AnimationPlayer), Without<Ragdoll>>,
+    velocities: Query<&LinearVelocity>,
+    characters: Query<(), With<Character>>,
+    skeleton: Skeleton,
+) {
+    for event in death_events.read() {
+        let mut rigged = false;
+        for (player_entity, animator, mut player) in &mut animators {
+            if animator.character != event.entity {
+                continue;
+            }
+            let Some(rig) = animations.rig(animator.rig) else {
+                continue;
+            };
+            let velocity = velocities.get(event.entity).map_or(Vec3::ZERO, |velocity| velocity.0);
+
+            // Every bone of a chain but the last starts a segment running to the next one
+            let mut segments: Vec<(Entity, Entity, bool)> = Vec::new(); // Start, end, and whether a segment comes before it in the chain
+            for chain in &rig.mask_groups {
+                let Some(bones) = skeleton.chain(player_entity, chain) else {
+                    continue;
+                };
+                for (index, pair) in bones.windows(2).enumerate() {
+                    segments.push((pair[0], pair[1], index > 0));
+                }
+            }
+            segments.sort_by_key(|&(bone, _, _)| skeleton.parents.iter_ancestors(bone).count());
+
+            let mut parts: Vec<RagdollPart> = Vec::new();
+            let mut joints = Vec::new();
+            for (bone, end, in_chain) in segments {
+                let (Ok((&rest, global)), Some(end)) = (skeleton.transforms.get(bone), skeleton.position(end)) else {
+                    continue;
+                };
+                let (scale, rotation, position) = global.to_scale_rotation_translation();
+                let segment = end - position;
+                if segment.length() < MIN_PART_LENGTH {
+                    continue;
+                }
+
+                let body = commands.spawn((
+                    Name::new("Ragdoll part"),
+                    RagdollBody,
+                    RigidBody::Dynamic,
+                    Collider::capsule_endpoints(segment.length() * PART_RADIUS, Vec3::ZERO, segment),
+                    CollisionLayers::new(GameLayer::Ragdoll, [GameLayer::Default]),
+                    Transform::from_translation(position),
+                    LinearVelocity(velocity),
+                    LinearDamping(LINEAR_DAMPING),
+                    AngularDamping(ANGULAR_DAMPING),
+                    StateScoped(InWorld),
+                )).id();
+
+                // Jointed to the nearest part above it, where its bone starts
+                let parent = skeleton
+                    .parents
+                    .iter_ancestors(bone)
+                    .find_map(|ancestor| parts.iter().find(|part| part.bone == ancestor))
+                    .and_then(|part| Some((part.body, skeleton.position(part.bone)?)));
+                if let Some((parent_body, parent_position)) = parent {
+                    let anchor = position - parent_position;
+                    let direction = segment.normalize();
+                    let joint = if in_chain {
+                        // Bends in the plane the chain was bent in at death
+                        let axis = anchor
+                            .cross(direction)
+                            .try_normalize()
+                            .unwrap_or_else(|| direction.any_orthonormal_vector());
+                        commands.spawn((
+                            RevoluteJoint::new(parent_body, body)
+                                .with_local_anchor_1(anchor)
+                                .with_aligned_axis(axis)
+                                .with_angle_limits(-HINGE_LIMIT, HINGE_LIMIT),
+                            StateScoped(InWorld),
+                        )).id()
+                    } else {
+                        commands.spawn((
+                            SphericalJoint::new(parent_body, body)
+                                .with_local_anchor_1(anchor)
+                                .with_twist_axis(direction)
+                                .with_swing_limits(-SWING_LIMIT, SWING_LIMIT)
+                                .with_twist_limits(-TWIST_LIMIT, TWIST_LIMIT),
+                            StateScoped(InWorld),
+                        )).id()
+                    };
+                    joints.push(joint);
+                }
+
+                parts.push(RagdollPart {
+                    bone,
+                    body,
+                    rest,
+                    rotation,
+                    scale,
+                    places_bone: parent.is_none(),
+                });
+            }
+
+            player.stop_all();
+            commands.entity(event.entity).insert((RigidBodyDisabled, ColliderDisabled));
+            commands.entity(player_entity).insert(Ragdoll {
+                parts,
+                joints,
+                settled_for: 0.0,
+                frozen: false,
+            });
+            rigged = true;
+        }
+
+        if !rigged && characters.contains(event.entity) {
+            // Free to tip over, and no longer tilted upright to the ground
+            commands.entity(event.entity)
+                .insert((BodyRagdoll::default(), LockedAxes::new()))
+                .remove::<GroundNormal>();
+        }
+    }
+}
+
+/// Runs after the animation, which no longer plays on a ragdoll. Turns each bone to match its body,
+/// and moves the topmost ones along with theirs.
+pub(super) fn pose_ragdolls(
+    ragdolls: Query<&Ragdoll>,
+    bodies: Query<&Transform, With<RagdollBody>>,
+    parents: Query<&ChildOf>,
+    mut transforms: Query<&mut Transform, Without<RagdollBody>>,
+) {
+    for ragdoll in &ragdolls {
+        for part in &ragdoll.parts {
+            let Ok(body) = bodies.get(part.body) else {
+                continue;
+            };
+            let Ok(parent) = parents.get(part.bone).map(|child_of| child_of.parent()) else {
+                continue;
+            };
+            let world = GlobalTransform::from(Transform {
+                translation: body.translation,
+                rotation: body.rotation * part.rotation,
+                scale: part.scale,
+            });
+            let local = world.reparented_to(&world_transform(parent, &transforms, &parents));
+
+            let Ok(mut transform) = transforms.get_mut(part.bone) else {
+                continue;
+            };
+            transform.rotation = local.rotation;
+            if part.places_bone {
+                transform.translation = local.translation;
+            }
+        }
+    }
+}
+
+/// Freezes ragdolls that have come to rest. The physics puts still bodies to sleep on its own,
+/// freezing them keeps a nudge from waking the body up again.
+pub(super) fn settle_ragdolls(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut ragdolls: Query<&mut Ragdoll>,
+    bodies: Query<(&LinearVelocity, &AngularVelocity), With<RagdollBody>>,
+    mut body_ragdolls: Query<(Entity, &mut BodyRagdoll, &LinearVelocity, &AngularVelocity)>,
+) {
+    for (entity, mut ragdoll, linear, angular) in &mut body_ragdolls {
+        if ragdoll.frozen {
+            continue;
+        }
+        let at_rest = linear.length() < SETTLE_SPEED && angular.length() < SETTLE_SPEED;
+        ragdoll.settled_for = if at_rest { ragdoll.settled_for + time.delta_secs() } else { 0.0 };
+        if ragdoll.settled_for >= SETTLE_TIME {
+            commands.entity(entity).insert(RigidBody::Static);
+            ragdoll.frozen = true;
+        }
+    }
+
+    for mut ragdoll in &mut ragdolls {
+        if ragdoll.frozen {
+            continue;
+        }
+
+        let at_rest = ragdoll.parts.iter().all(|part| {
+            bodies
+                .get(part.body)
+                .is_ok_and(|(linear, angular)| linear.length() < SETTLE_SPEED && angular.length() < SETTLE_SPEED)
+        });
+        ragdoll.settled_for = if at_rest { ragdoll.settled_for + time.delta_secs() } else { 0.0 };
+        if ragdoll.settled_for < SETTLE_TIME {
+            continue;
+        }
+
+        for part in &ragdoll.parts {
+            commands.entity(part.body).insert(RigidBody::Static);
+        }
+        ragdoll.frozen = true;
+    }
+}
+
+/// Gets characters back up once they're no longer dead, after a respawn. The ragdoll is removed,
+/// the bones go back to where the animation had them and the clips start over. A [`BodyRagdoll`] is
+/// stood back up.
+pub(super) fn clear_ragdolls(
+    mut commands: Commands,
+    mut ragdolls: Query<(Entity, &Ragdoll, &mut LocomotionAnimator)>,
+    body_ragdolls: Query<Entity, With<BodyRagdoll>>,
+    health: Query<&Health>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for entity in &body_ragdolls {
+        if health.get(entity).is_ok_and(Health::is_dead) {
+            continue;
+        }
+
+        // Back on its feet, facing the way it fell
+        if let Ok(mut transform) = transforms.get_mut(entity) {
+            let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+            transform.rotation = Quat::from_rotation_y(yaw);
+        }
+        commands.entity(entity)
+            .remove::<BodyRagdoll>()
+            .insert((RigidBody::Dynamic, LockedAxes::ROTATION_LOCKED, AngularVelocity::ZERO, GroundNormal::new()));
+    }
+
+    for (entity, ragdoll, mut animator) in &mut ragdolls {
+        if health.get(animator.character).is_ok_and(Health::is_dead) {
+            continue;
+        }
+
+        for part in &ragdoll.parts {
+            if let Ok(mut transform) = transforms.get_mut(part.bone) {
+                *transform = part.rest;
+            }
+            commands.entity(part.body).despawn();
+        }
+        for &joint in &ragdoll.joints {
+            commands.entity(joint).despawn();
+        }
+        commands.entity(animator.character).remove::<(RigidBodyDisabled, ColliderDisabled)>();
+        commands.entity(entity).remove::<Ragdoll>();
+        animator.restart();
+    }
+}
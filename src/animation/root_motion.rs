@@ -0,0 +1,101 @@
+use bevy::animation::AnimationTarget;
+use bevy::prelude::*;
+use crate::character_controller::Character;
+use super::foot_ik::world_transform;
+use super::{Animations, LocomotionAnimator, LocomotionState};
+
+/// Takes the horizontal movement of a model's root bone out of its roll and attack clips and hands it
+/// to the character as [`Character::root_motion`], so the body moves the way the clip was authored
+#[derive(Component, Default)]
+pub struct RootMotion {
+    bone: Option<Entity>,              // Found once the skeleton has spawned
+    action: Option<LocomotionState>,   // Roll or attack being extracted from
+    action_elapsed: Option<f32>,       // How far into it the character was last frame
+    anchor: Vec3,                      // Bone translation when the action started, held there horizontally
+    animated: Vec3,                    // Bone translation the clip gave it last frame
+    written: Option<Vec3>,             // Bone translation set last frame
+}
+
+/// The roll or attack whose clip moves the character, if it's doing one
+fn root_motion_action(character: &Character) -> Option<LocomotionState> {
+    if character.is_rolling {
+        Some(LocomotionState::Roll)
+    } else if character.is_attacking {
+        LocomotionState::upper_body(character)
+    } else {
+        None
+    }
+}
+
+/// Runs after the animation has posed the skeleton. The root bone's horizontal movement since last
+/// frame becomes the character's velocity, and the bone is pulled back over the body, which now
+/// does the moving.
+pub(super) fn extract_root_motion(
+    time: Res<Time>,
+    animations: Res<Animations>,
+    mut animators: Query<(Entity, &LocomotionAnimator, &mut RootMotion)>,
+    mut characters: Query<&mut Character>,
+    targets: Query<(Entity, &AnimationTarget)>,
+    parents: Query<&ChildOf>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let delta = time.delta_secs();
+
+    for (player, animator, mut root_motion) in &mut animators {
+        let Ok(mut character) = characters.get_mut(animator.character) else {
+            continue;
+        };
+
+        if root_motion.bone.is_none() {
+            let id = animations.rig(animator.rig).and_then(|rig| rig.root_motion);
+            root_motion.bone = targets
+                .iter()
+                .find(|(_, target)| target.player == player && Some(target.id) == id)
+                .map(|(entity, _)| entity);
+        }
+        let Some(bone) = root_motion.bone else {
+            continue;
+        };
+        let Ok(parent) = parents.get(bone).map(|child_of| child_of.parent()) else {
+            continue;
+        };
+        // Turns a change in the bone's translation into its movement over the ground
+        let parent_world = world_transform(parent, &transforms, &parents).affine();
+        let horizontal = |local: Vec3| parent_world.transform_vector3(local) * Vec3::new(1.0, 0.0, 1.0);
+        let to_local = parent_world.inverse();
+
+        let Ok(mut transform) = transforms.get_mut(bone) else {
+            continue;
+        };
+        // Clips that don't animate the bone's translation leave last frame's pinned one in place
+        let animated = match root_motion.written {
+            Some(written) if transform.translation == written => root_motion.animated,
+            _ => transform.translation,
+        };
+
+        let action = root_motion_action(&character);
+        let action_elapsed = action.and_then(|action| action.action_elapsed(&character));
+        let restarted = action_elapsed
+            .zip(root_motion.action_elapsed)
+            .is_some_and(|(now, before)| now < before);
+        if action != root_motion.action || restarted {
+            root_motion.anchor = animated;
+            root_motion.animated = animated;
+        }
+        root_motion.action = action;
+        root_motion.action_elapsed = action_elapsed;
+
+        if action.is_none() || delta <= 0.0 {
+            character.root_motion = None;
+            root_motion.written = None;
+            continue;
+        }
+
+        character.root_motion = Some(horizontal(animated - root_motion.animated) / delta);
+
+        // Keep the model over the body, only the clip's vertical movement stays in the pose
+        transform.translation = animated - to_local.transform_vector3(horizontal(animated - root_motion.anchor));
+        root_motion.animated = animated;
+        root_motion.written = Some(transform.translation);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::config::{self, RonFileError};
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ActionMap::load_or_default());
+    }
+}
+
+const BINDINGS_PATH: &str = "config/bindings.ron";
+
+// How far a stick has to be pushed for an axis binding to count as pressed
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Something the player can do, bound to any number of inputs in the [`ActionMap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Jump,
+    Roll,
+    Block,
+    LightAttack,
+    HeavyAttack,
+    LockOn,
+    Interact,
+}
+
+impl InputAction {
+    /// Every action, in the order they are listed on the controls screen
+    pub const ALL: [InputAction; 12] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Sprint,
+        InputAction::Jump,
+        InputAction::Roll,
+        InputAction::Block,
+        InputAction::LightAttack,
+        InputAction::HeavyAttack,
+        InputAction::LockOn,
+        InputAction::Interact,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputAction::MoveUp => "Move Forward",
+            InputAction::MoveDown => "Move Back",
+            InputAction::MoveLeft => "Move Left",
+            InputAction::MoveRight => "Move Right",
+            InputAction::Sprint => "Sprint",
+            InputAction::Jump => "Jump",
+            InputAction::Roll => "Roll",
+            InputAction::Block => "Block",
+            InputAction::LightAttack => "Light Attack",
+            InputAction::HeavyAttack => "Heavy Attack",
+            InputAction::LockOn => "Lock On",
+            InputAction::Interact => "Interact",
+        }
+    }
+}
+
+/// Which way a stick has to be pushed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+/// A physical input that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+    GamepadAxis(GamepadAxis, AxisDirection), // Analog, only held and never "just pressed"
+}
+
+impl Binding {
+    /// Bindings of the same kind take each other's place when rebinding: keys and mouse buttons,
+    /// gamepad buttons, and sticks
+    fn same_kind(&self, other: &Binding) -> bool {
+        match (self, other) {
+            (Binding::Key(_) | Binding::Mouse(_), Binding::Key(_) | Binding::Mouse(_)) => true,
+            (Binding::Gamepad(_), Binding::Gamepad(_)) => true,
+            (Binding::GamepadAxis(..), Binding::GamepadAxis(..)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => {
+                let name = format!("{key:?}");
+                let name = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")).unwrap_or(&name);
+                write!(f, "{name}")
+            }
+            Binding::Mouse(button) => write!(f, "Mouse {button:?}"),
+            Binding::Gamepad(button) => write!(f, "Pad {button:?}"),
+            Binding::GamepadAxis(axis, direction) => {
+                let sign = if *direction == AxisDirection::Positive { "+" } else { "-" };
+                write!(f, "Pad {axis:?}{sign}")
+            }
+        }
+    }
+}
+
+/// The inputs bound to each action, read from and saved to `config/bindings.ron`
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap(pub HashMap<InputAction, Vec<Binding>>);
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        use Binding::{Key, Mouse};
+        let pad = Binding::Gamepad;
+        let stick = Binding::GamepadAxis;
+        Self(HashMap::from([
+            (InputAction::MoveUp, vec![Key(KeyCode::KeyW), Key(KeyCode::ArrowUp), stick(GamepadAxis::LeftStickY, AxisDirection::Positive)]),
+            (InputAction::MoveDown, vec![Key(KeyCode::KeyS), Key(KeyCode::ArrowDown), stick(GamepadAxis::LeftStickY, AxisDirection::Negative)]),
+            (InputAction::MoveLeft, vec![Key(KeyCode::KeyA), Key(KeyCode::ArrowLeft), stick(GamepadAxis::LeftStickX, AxisDirection::Negative)]),
+            (InputAction::MoveRight, vec![Key(KeyCode::KeyD), Key(KeyCode::ArrowRight), stick(GamepadAxis::LeftStickX, AxisDirection::Positive)]),
+            (InputAction::Sprint, vec![Key(KeyCode::ShiftLeft), Key(KeyCode::ShiftRight), pad(GamepadButton::RightTrigger2)]),
+            (InputAction::Jump, vec![Key(KeyCode::Space), pad(GamepadButton::South)]),
+            (InputAction::Roll, vec![Key(KeyCode::ControlLeft), pad(GamepadButton::East)]),
+            (InputAction::Block, vec![Mouse(MouseButton::Right), pad(GamepadButton::RightTrigger)]),
+            (InputAction::LightAttack, vec![Mouse(MouseButton::Left), pad(GamepadButton::West)]),
+            (InputAction::HeavyAttack, vec![Key(KeyCode::KeyF), pad(GamepadButton::North)]),
+            (InputAction::LockOn, vec![Key(KeyCode::KeyQ), Mouse(MouseButton::Middle), pad(GamepadButton::RightThumb)]),
+            (InputAction::Interact, vec![Key(KeyCode::KeyE), pad(GamepadButton::DPadUp)]),
+        ]))
+    }
+}
+
+impl ActionMap {
+    pub fn bindings(&self, action: InputAction) -> &[Binding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces the action's first binding of the same kind as `binding`, or adds it if there's none.
+    /// Rebinding Move Forward to a key swaps out W but keeps the arrow key and the stick.
+    pub fn rebind(&mut self, action: InputAction, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        if bindings.contains(&binding) {
+            return;
+        }
+        match bindings.iter_mut().find(|existing| existing.same_kind(&binding)) {
+            Some(existing) => *existing = binding,
+            None => bindings.push(binding),
+        }
+    }
+
+    /// Bindings as text for prompts and the controls screen, e.g. "E / Pad DPadUp"
+    pub fn describe(&self, action: InputAction) -> String {
+        let bindings = self.bindings(action);
+        if bindings.is_empty() {
+            return "Unbound".to_string();
+        }
+        bindings.iter().map(ToString::to_string).collect::<Vec<_>>().join(" / ")
+    }
+
+    /// Reads the saved bindings, falling back to the defaults if there are none or they can't be read.
+    /// Actions missing from the file keep their default bindings.
+    pub fn load_or_default() -> Self {
+        let saved: ActionMap = config::load_ron_or_default(BINDINGS_PATH);
+        let mut map = Self::default();
+        map.0.extend(saved.0);
+        map
+    }
+
+    pub fn save(&self) -> Result<(), RonFileError> {
+        config::save_ron(BINDINGS_PATH, self)
+    }
+}
+
+/// Reads actions from the keyboard, mouse and every gamepad through the [`ActionMap`]
+#[derive(SystemParam)]
+pub struct ActionInput<'w, 's> {
+    map: Res<'w, ActionMap>,
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+
+impl ActionInput<'_, '_> {
+    /// How strongly a binding is held, from 0 to 1. Buttons are either 0 or 1, sticks anything between.
+    fn binding_value(&self, binding: &Binding) -> f32 {
+        match binding {
+            Binding::Key(key) => self.keyboard.pressed(*key) as u8 as f32,
+            Binding::Mouse(button) => self.mouse.pressed(*button) as u8 as f32,
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| gamepad.pressed(*button)) as u8 as f32,
+            Binding::GamepadAxis(axis, direction) => self
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| gamepad.get(*axis))
+                .map(|value| match direction {
+                    AxisDirection::Positive => value.max(0.0),
+                    AxisDirection::Negative => (-value).max(0.0),
+                })
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// How strongly the action is held, the strongest of its bindings
+    pub fn value(&self, action: InputAction) -> f32 {
+        self.map
+            .bindings(action)
+            .iter()
+            .map(|binding| self.binding_value(binding))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.value(action) >= AXIS_PRESS_THRESHOLD
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.map.bindings(action).iter().any(|binding| match binding {
+            Binding::Key(key) => self.keyboard.just_pressed(*key),
+            Binding::Mouse(button) => self.mouse.just_pressed(*button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| gamepad.just_pressed(*button)),
+            Binding::GamepadAxis(..) => false,
+        })
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.map.bindings(action).iter().any(|binding| match binding {
+            Binding::Key(key) => self.keyboard.just_released(*key),
+            Binding::Mouse(button) => self.mouse.just_released(*button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| gamepad.just_released(*button)),
+            Binding::GamepadAxis(..) => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_a_key_keeps_the_other_bindings() {
+        let mut map = ActionMap::default();
+        map.rebind(InputAction::MoveUp, Binding::Key(KeyCode::KeyI));
+        assert_eq!(map.bindings(InputAction::MoveUp), &[
+            Binding::Key(KeyCode::KeyI),
+            Binding::Key(KeyCode::ArrowUp),
+            Binding::GamepadAxis(GamepadAxis::LeftStickY, AxisDirection::Positive),
+        ]);
+    }
+
+    #[test]
+    fn rebinding_a_pad_button_keeps_the_stick() {
+        let mut map = ActionMap::default();
+        map.rebind(InputAction::MoveUp, Binding::Gamepad(GamepadButton::DPadUp));
+        assert_eq!(map.bindings(InputAction::MoveUp).len(), 4);
+
+        map.rebind(InputAction::MoveUp, Binding::Gamepad(GamepadButton::North));
+        assert_eq!(map.bindings(InputAction::MoveUp)[3], Binding::Gamepad(GamepadButton::North));
+        assert_eq!(map.bindings(InputAction::MoveUp).len(), 4);
+    }
+}
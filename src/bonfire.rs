@@ -0,0 +1,227 @@
+use bevy::prelude::*;
+use crate::bindings::{ActionInput, ActionMap, InputAction};
+use crate::character_controller::{Character, Grounded};
+use crate::combat::{Focus, Health};
+use crate::game_states::{AppState, InWorld};
+use crate::perception::Perception;
+use crate::player::{Player, SPAWN_POSITION};
+
+pub struct BonfirePlugin;
+
+impl Plugin for BonfirePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RestEvent>()
+            .init_resource::<RespawnPoint>()
+            .add_systems(OnEnter(InWorld), setup)
+            .add_systems(Update, (
+                update_rest_prompt,
+                rest_at_bonfire,
+                flicker_bonfires,
+            ).chain().run_if(in_state(AppState::InGame)))
+            // Bindings are changed from the pause menu
+            .add_systems(Update, update_rest_prompt_text
+                .run_if(resource_changed::<ActionMap>)
+                .run_if(in_state(InWorld)));
+    }
+}
+
+/// Where each bonfire stands in the level
+const BONFIRES: [(&str, Vec3); 2] = [
+    ("Fox Den", Vec3::new(18.0, 0.0, 18.0)),
+    ("Guard Post", Vec3::new(16.0, 0.0, 32.0)),
+];
+
+// How close the player has to stand to rest
+const REST_RADIUS: f32 = 1.5;
+
+const EMBER_COLOR: Color = Color::srgb(1.0, 0.45, 0.1);
+const FIRE_LIGHT_INTENSITY: f32 = 40_000.0;
+
+/// A checkpoint the player can rest at
+#[derive(Component, Debug)]
+pub struct Bonfire {
+    pub rest_radius: f32,
+}
+
+/// Where the player comes back after dying, moved by resting at a bonfire
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct RespawnPoint {
+    pub position: Vec3,
+    pub bonfire: Option<Entity>, // Last bonfire rested at, None before resting at any
+}
+
+impl Default for RespawnPoint {
+    fn default() -> Self {
+        Self {
+            position: SPAWN_POSITION,
+            bonfire: None,
+        }
+    }
+}
+
+/// Sent when the player rests at a bonfire. Enemies and props listen for it to reset the world.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RestEvent {
+    pub bonfire: Entity,
+}
+
+/// Marker for the "rest" hint shown while standing at a bonfire
+#[derive(Component)]
+struct RestPrompt;
+
+/// The hint's text, which names the interact binding
+#[derive(Component)]
+struct RestPromptText;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    action_map: Res<ActionMap>,
+) {
+    // A new world starts back at the default spawn
+    commands.insert_resource(RespawnPoint::default());
+
+    let mesh = meshes.add(Cone::new(0.2, 0.5));
+    let material = materials.add(StandardMaterial {
+        base_color: EMBER_COLOR,
+        emissive: LinearRgba::from(EMBER_COLOR) * 8.0,
+        ..default()
+    });
+
+    for (name, position) in BONFIRES {
+        commands.spawn((
+            Name::new(name),
+            Bonfire { rest_radius: REST_RADIUS },
+            StateScoped(InWorld),
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(position + Vec3::Y * 0.25),
+            children![(
+                PointLight {
+                    color: EMBER_COLOR,
+                    intensity: FIRE_LIGHT_INTENSITY,
+                    range: 8.0,
+                    shadows_enabled: true,
+                    ..default()
+                },
+                Transform::from_xyz(0.0, 0.5, 0.0),
+            )],
+        ));
+    }
+
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(20.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+        RestPrompt,
+        StateScoped(InWorld),
+        children![(
+            Text::new(rest_prompt_text(&action_map)),
+            RestPromptText,
+            TextFont {
+                font_size: 24.0,
+                ..default()
+            },
+            TextColor(Color::srgb(0.9, 0.9, 0.9)),
+        )],
+    ));
+}
+
+fn rest_prompt_text(action_map: &ActionMap) -> String {
+    format!("Press {} to rest", action_map.describe(InputAction::Interact))
+}
+
+fn update_rest_prompt_text(action_map: Res<ActionMap>, mut texts: Query<&mut Text, With<RestPromptText>>) {
+    for mut text in &mut texts {
+        **text = rest_prompt_text(&action_map);
+    }
+}
+
+/// The bonfire the player is close enough to rest at, if any
+fn bonfire_in_reach(
+    player_position: Vec3,
+    bonfires: &Query<(Entity, &Bonfire, &GlobalTransform)>,
+) -> Option<Entity> {
+    bonfires
+        .iter()
+        .find(|(_, bonfire, transform)| transform.translation().distance(player_position) <= bonfire.rest_radius)
+        .map(|(entity, _, _)| entity)
+}
+
+/// Whether the player is free to sit down: on the ground, not mid-action and nobody hunting them
+fn can_rest(character: &Character, grounded: bool, perceivers: &Query<&Perception>) -> bool {
+    grounded
+        && !character.is_rolling
+        && !character.is_attacking
+        && !character.is_recovering
+        && !perceivers.iter().any(|perception| perception.is_aggroed())
+}
+
+fn update_rest_prompt(
+    players: Query<(&GlobalTransform, &Character, Has<Grounded>), With<Player>>,
+    bonfires: Query<(Entity, &Bonfire, &GlobalTransform)>,
+    perceivers: Query<&Perception>,
+    mut prompts: Query<&mut Visibility, With<RestPrompt>>,
+) {
+    let show = players.single().is_ok_and(|(transform, character, grounded)| {
+        bonfire_in_reach(transform.translation(), &bonfires).is_some() && can_rest(character, grounded, &perceivers)
+    });
+
+    for mut visibility in &mut prompts {
+        *visibility = if show { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+/// Resting refills the player, moves the respawn point here and resets the world
+fn rest_at_bonfire(
+    input: ActionInput,
+    mut players: Query<(&GlobalTransform, &mut Character, &mut Health, &mut Focus, Has<Grounded>), With<Player>>,
+    bonfires: Query<(Entity, &Bonfire, &GlobalTransform)>,
+    perceivers: Query<&Perception>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut rest_events: EventWriter<RestEvent>,
+) {
+    if !input.just_pressed(InputAction::Interact) {
+        return;
+    }
+
+    let Ok((transform, mut character, mut health, mut focus, grounded)) = players.single_mut() else { return };
+    let Some(bonfire) = bonfire_in_reach(transform.translation(), &bonfires) else { return };
+    if !can_rest(&character, grounded, &perceivers) {
+        return;
+    }
+
+    character.reset_state();
+    health.refill();
+    focus.refill();
+
+    // Come back exactly where we sat down, which is known to be safe ground
+    *respawn_point = RespawnPoint {
+        position: transform.translation(),
+        bonfire: Some(bonfire),
+    };
+
+    rest_events.write(RestEvent { bonfire });
+}
+
+/// Makes the bonfire lights flicker
+fn flicker_bonfires(
+    time: Res<Time>,
+    mut lights: Query<(&mut PointLight, &ChildOf)>,
+    bonfires: Query<(), With<Bonfire>>,
+) {
+    let t = time.elapsed_secs();
+    for (mut light, child_of) in &mut lights {
+        if !bonfires.contains(child_of.parent()) {
+            continue;
+        }
+        let flicker = (t * 13.0).sin() * 0.5 + (t * 7.3 + child_of.parent().index() as f32).sin() * 0.5;
+        light.intensity = FIRE_LIGHT_INTENSITY * (1.0 + 0.15 * flicker);
+    }
+}
@@ -1,10 +1,14 @@
 use bevy::prelude::*;
 use avian3d::prelude::*;
+use std::collections::HashSet;
 use std::time::Duration;
 use bevy::gltf::{GltfMesh, GltfNode};
 use rand::prelude::IteratorRandom;
 use rand::Rng;
-use crate::game_states::AppState;
+use crate::bonfire::RestEvent;
+use crate::combat::DamageEvent;
+use crate::game_states::{AppState, InWorld};
+use crate::perception::NoiseEvent;
 
 /// Plugin to handle all breakable prop functionality in the game
 pub struct BreakablePropsPlugin;
@@ -18,10 +22,15 @@ impl Plugin for BreakablePropsPlugin {
             .register_type::<GltfBreakPattern>()
             .register_type::<FracturePattern>()
             .add_event::<BreakPropEvent>()
-            .add_systems(OnEnter(AppState::InGame), setup)
+            .init_resource::<DestroyedProps>()
+            .add_systems(OnEnter(InWorld), setup)
+            .add_systems(Update, (clear_props, setup)
+                .chain()
+                .run_if(on_event::<RestEvent>))
             .add_systems(FixedUpdate, (
                 detect_breakable_collisions,
-                break_props.after(detect_breakable_collisions),
+                break_on_damage,
+                break_props.after(detect_breakable_collisions).after(break_on_damage),
                 despawn_broken_pieces,
             ).run_if(in_state(AppState::InGame)))
             ;
@@ -32,7 +41,7 @@ impl Plugin for BreakablePropsPlugin {
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 #[require(RigidBody)]
-struct Breakable {
+pub struct Breakable {
     /// Minimum impulse required to break the prop
     pub break_threshold: f32,
     /// Initial impulse to apply to the pieces when broken
@@ -41,6 +50,10 @@ struct Breakable {
     pub despawn_delay: f32,
 }
 
+/// Names of the props broken since the last rest
+#[derive(Resource, Debug, Default)]
+pub struct DestroyedProps(pub HashSet<String>);
+
 /// Component to control procedural breaking settings
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
@@ -180,6 +193,7 @@ impl Default for ImpactSettings {
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 #[require(LinearDamping, AngularDamping, Restitution, Friction, RigidBody)] // Pieces always have physics components
+#[require(StateScoped<InWorld> = StateScoped(InWorld))] // Pieces still flying are cleared with the world
 struct BrokenPiece {
     pub timer: Timer,
     pub original_position: Vec3,
@@ -268,17 +282,50 @@ fn detect_breakable_collisions(
     }
 }
 
+/// System to break props that were hit by an attack, regardless of collision velocity
+fn break_on_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut break_events: EventWriter<BreakPropEvent>,
+    breakables: Query<&GlobalTransform, With<Breakable>>,
+    transforms: Query<&GlobalTransform>,
+) {
+    for event in damage_events.read() {
+        let Ok(transform) = breakables.get(event.target) else {
+            continue;
+        };
+
+        // Put the impact on the attacker's side so the pieces fly away from them
+        let impact_point = event.source
+            .and_then(|source| transforms.get(source).ok())
+            .map_or(transform.translation(), |source| {
+                source.translation().lerp(transform.translation(), 0.5)
+            });
+
+        break_events.write(BreakPropEvent {
+            entity: event.target,
+            impact_point,
+            impact_force: event.amount,
+        });
+    }
+}
+
+// How far the sound of a prop breaking carries
+const BREAK_NOISE_RADIUS: f32 = 12.0;
+
 /// System to handle breaking props with improved physics and effects
 fn break_props(
     mut commands: Commands,
     mut break_events: EventReader<BreakPropEvent>,
+    mut noise_events: EventWriter<NoiseEvent>,
+    mut destroyed_props: ResMut<DestroyedProps>,
     breakables: Query<(
         Entity,
         &Breakable,
         &GlobalTransform,
         Option<&ImpactSettings>,
         Option<&ProceduralBreakSettings>,
-        Option<&GltfBreakPattern>
+        Option<&GltfBreakPattern>,
+        Option<&Name>,
     )>,
     asset_server: Res<AssetServer>,
     gltf_assets: Res<Assets<Gltf>>,
@@ -296,15 +343,19 @@ fn break_props(
                   global_transform,
                   impact_settings,
                   procedural_settings,
-                  gltf_pattern
+                  gltf_pattern,
+                  name,
               )) =
             breakables.get(event.entity)
         {
             // Get default settings or use custom ones
             let impact = impact_settings.cloned().unwrap_or_default();
 
-            // Despawn the original intact prop
+            // Despawn the original intact prop, remembering it so it stays broken after loading
             commands.entity(entity).despawn();
+            if let Some(name) = name {
+                destroyed_props.0.insert(name.to_string());
+            }
 
             // Get the original position
             let original_pos = global_transform.translation();
@@ -359,6 +410,13 @@ fn break_props(
                 }
             }
 
+            // Smashing props alerts anyone nearby
+            noise_events.write(NoiseEvent {
+                position: original_pos,
+                radius: BREAK_NOISE_RADIUS,
+                source: None,
+            });
+
             // Play break sound
             if impact.play_sound {
                 commands.spawn(AudioPlayer::new(asset_server.load("sounds/breaking.ogg")));
@@ -538,16 +596,31 @@ fn despawn_broken_pieces(
     }
 }
 
+/// Removes props and their leftover pieces so resting can put every prop back intact
+fn clear_props(
+    mut commands: Commands,
+    props: Query<Entity, Or<(With<Breakable>, With<BrokenPiece>)>>,
+) {
+    for entity in &props {
+        commands.entity(entity).despawn();
+    }
+}
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
+    // Every prop is intact again
+    commands.insert_resource(DestroyedProps::default());
+
     let mut collider_height = 0.5;
     let collider_height_offset = 0.1;
     // Creating a breakable vase with GLTF node-based pieces
     commands.spawn((
+        Name::new("Vase"),
+        StateScoped(InWorld),
         SceneRoot(asset_server.load("models/intact_vase.glb#Scene0")),
         Transform::from_xyz(-5.0, collider_height + collider_height_offset, 0.0),
         Collider::capsule(0.5, collider_height),
@@ -576,6 +649,8 @@ fn setup(
     // Add procedural breakable objects
     collider_height = 0.4;
     commands.spawn((
+        Name::new("Clay Pot"),
+        StateScoped(InWorld),
         Mesh3d(meshes.add(Sphere::new(collider_height))),
         MeshMaterial3d(materials.add(Color::srgb(0.8, 0.4, 0.3))),
         Transform::from_xyz(-2.0, collider_height + collider_height_offset, -1.0),
@@ -601,6 +676,8 @@ fn setup(
     // Add a crate with different breaking properties
     collider_height = 0.25;
     commands.spawn((
+        Name::new("Crate"),
+        StateScoped(InWorld),
         Mesh3d(meshes.add(Cuboid::new(0.5, 0.5, 0.5))),
         MeshMaterial3d(materials.add(Color::srgb(0.6, 0.4, 0.2))),
         Collider::cuboid(0.25, collider_height, 0.25),
@@ -1,16 +1,16 @@
 use bevy::{
-    core_pipeline::{bloom::Bloom, experimental::taa::{TemporalAntiAliasPlugin, TemporalAntiAliasing}, motion_blur::MotionBlur, tonemapping::Tonemapping, Skybox},
-    input::{
-        keyboard::KeyCode, mouse::{MouseMotion, MouseWheel}
-    },
-    pbr::{ScreenSpaceAmbientOcclusion, ScreenSpaceAmbientOcclusionQualityLevel, VolumetricFog},
+    core_pipeline::{experimental::taa::TemporalAntiAliasPlugin, tonemapping::Tonemapping, Skybox},
+    input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
     math::StableInterpolate
 };
+use std::f32::consts::{PI, TAU};
 use avian3d::prelude::*;
-use bevy::pbr::{Atmosphere, AtmosphereSettings};
 use bevy::render::camera::Exposure;
-use crate::game_states::AppState;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+use crate::game_states::{AppState, InWorld};
+use crate::graphics::GraphicsSettings;
+use crate::lock_on::{locked_target_focus, LockOn, Targetable};
 use crate::player::Player;
 
 #[derive(Component)]
@@ -48,54 +48,23 @@ impl Default for ThirdPersonCamera {
 pub fn spawn_camera(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    graphics: Res<GraphicsSettings>,
 ) {
-    commands.spawn((
+    let mut camera = commands.spawn((
         Camera3d::default(),
         Camera {
             hdr: true,
             ..default()
         },
+        StateScoped(InWorld),
 
-        Bloom::NATURAL,
         Tonemapping::TonyMcMapface,
-        // Msaa is off to let ssao work.
-        Msaa::Off,
-        ScreenSpaceAmbientOcclusion::default(),
-        TemporalAntiAliasing::default(),
-
-        MotionBlur{
-            samples: 8,
-            shutter_angle: 1.5,
-            ..default()
-        },
-        VolumetricFog {
-            ambient_intensity: 0.1,
-            ..default()
-        },
-        DistanceFog{
-            color: Color::srgb_u8(43, 44, 100),
-            falloff: FogFalloff::Exponential{
-                density: 15e-3,
-            },
-            ..default()
-        },
-
-        EnvironmentMapLight{
-            diffuse_map: asset_server.load("environment_maps/pisa_diffuse_rgb9e5_zstd.ktx2"),
-            specular_map: asset_server.load("environment_maps/pisa_specular_rgb9e5_zstd.ktx2"),
-            intensity: 2000.0,
-            ..default()
-        },
 
         // Add third-person camera controller
         ThirdPersonCamera::default(),
-        Atmosphere::EARTH,
-        AtmosphereSettings{
-            ..Default::default()
-        },
         Exposure::SUNLIGHT
 
-    ))
+    ));
         /*
     .insert(Skybox{
             image: asset_server.load("environment_maps/pisa_specular_rgb9e5_zstd.ktx2"),
@@ -103,10 +72,9 @@ pub fn spawn_camera(
             ..default()
     })
          */
-    .insert(ScreenSpaceAmbientOcclusion{
-        quality_level: ScreenSpaceAmbientOcclusionQualityLevel::High,
-        constant_object_thickness: 4.0,
-    });
+
+    // Bloom, fog, SSAO and the rest of the post-processing stack depend on the quality settings
+    graphics.apply_to_camera(&mut camera, &asset_server);
 }
 
 
@@ -114,22 +82,19 @@ pub fn spawn_camera(
 pub fn third_person_camera(
     mut mouse_motion: EventReader<MouseMotion>,
     mut mouse_wheel: EventReader<MouseWheel>,
-    keyboard: Res<ButtonInput<KeyCode>>,
     gamepads: Query<&Gamepad>,
-    player_query: Query<&Transform, (With<Player>, Without<ThirdPersonCamera>)>,
+    player_query: Query<(&Transform, Option<&LockOn>), (With<Player>, Without<ThirdPersonCamera>)>,
     mut camera_query: Query<(&mut Transform, &mut ThirdPersonCamera)>,
+    lock_targets: Query<(&GlobalTransform, &Targetable)>,
     time: Res<Time>,
-    mut exit: EventWriter<AppExit>,
 ) {
-    // Handle ESC key to exit the game
-    if keyboard.just_pressed(KeyCode::Escape) {
-        exit.write(AppExit::default());
-    }
-
     // Only update if we have a player and a camera
-    if let (Ok(player_transform), Ok((mut camera_transform, mut camera_params))) =
+    if let (Ok((player_transform, lock_on)), Ok((mut camera_transform, mut camera_params))) =
         (player_query.single(), camera_query.single_mut()) {
 
+        // While locked on the camera yaw follows the target instead of the mouse or stick
+        let lock_target = locked_target_focus(lock_on, &lock_targets);
+
         // Handle mouse input for camera rotation
         // Update camera rotation based on mouse movement
         for event in mouse_motion.read() {
@@ -138,7 +103,9 @@ pub fn third_person_camera(
             let dy = if camera_params.invert_y { -event.delta.y } else { event.delta.y };
 
             // Apply rotation speed
-            camera_params.yaw -= dx * camera_params.rotation_speed;
+            if lock_target.is_none() {
+                camera_params.yaw -= dx * camera_params.rotation_speed;
+            }
             camera_params.pitch += dy * camera_params.rotation_speed;
 
             // Clamp pitch to prevent flipping (limit how far up/down the camera can look)
@@ -155,7 +122,7 @@ pub fn third_person_camera(
         // GAMEPAD CAMERA CONTROL
         // Check for any connected gamepads
         for gamepad in gamepads.iter() {
-            // Use right stick for camera rotation
+            // Use right stick for camera rotation, while locked on it switches targets instead
             if let (Some(right_stick_x), Some(right_stick_y)) = (
                 gamepad.get(GamepadAxis::RightStickX),
                 gamepad.get(GamepadAxis::RightStickY),
@@ -173,7 +140,9 @@ pub fn third_person_camera(
                     let dy = if camera_params.invert_y { -inverted_stick_y } else { inverted_stick_y };
 
                     // Apply rotation with time-based smoothing
-                    camera_params.yaw -= dx * gamepad_sensitivity * time.delta_secs() * 60.0;
+                    if lock_target.is_none() {
+                        camera_params.yaw -= dx * gamepad_sensitivity * time.delta_secs() * 60.0;
+                    }
                     camera_params.pitch += dy * gamepad_sensitivity * time.delta_secs() * 60.0;
 
                     // Clamp pitch to prevent flipping
@@ -188,6 +157,16 @@ pub fn third_person_camera(
         // Get player position as the center point
         let player_pos = player_transform.translation;
 
+        // Swing around behind the player so the target stays in front of the camera
+        if let Some(target) = lock_target {
+            let to_target = target - player_pos;
+            if to_target.x != 0.0 || to_target.z != 0.0 {
+                let desired_yaw = f32::atan2(to_target.x, to_target.z);
+                let yaw_difference = (desired_yaw - camera_params.yaw + PI).rem_euclid(TAU) - PI;
+                camera_params.yaw += yaw_difference * (1.0 - (-camera_params.smoothness * time.delta_secs()).exp());
+            }
+        }
+
         // Create rotation quaternions from euler angles
         let pitch_rot = Quat::from_rotation_x(camera_params.pitch);
         let yaw_rot = Quat::from_rotation_y(camera_params.yaw);
@@ -197,15 +176,14 @@ pub fn third_person_camera(
         let camera_offset = camera_rotation * Vec3::new(
             0.0,
             camera_params.height_offset,
-            camera_params.distance // Positive distance is behind in orbital coordinates
+            framing_distance(&camera_params, player_pos, lock_target) // Positive distance is behind in orbital coordinates
         );
 
         // The camera should be positioned behind the player
         let target_position = player_pos - camera_offset;
 
         // Calculate the focus point (where the camera should look)
-        // Look at the player position with a slight height offset but don't use target_offset
-        let focus_pos = player_pos + Vec3::new(0.0, camera_params.height_offset * 0.5, 0.0);
+        let focus_pos = camera_focus(&camera_params, player_pos, lock_target);
 
         // Apply smoothing for camera movement (creates a more natural following effect)
         let mut position = camera_transform.translation;
@@ -221,18 +199,35 @@ pub fn third_person_camera(
     }
 }
 
+/// Where the camera looks: the player, or halfway to a locked-on target to keep both in frame
+fn camera_focus(camera_params: &ThirdPersonCamera, player_pos: Vec3, lock_target: Option<Vec3>) -> Vec3 {
+    let player_focus = player_pos + Vec3::new(0.0, camera_params.height_offset * 0.5, 0.0);
+    match lock_target {
+        Some(target) => player_focus.lerp(target, 0.5),
+        None => player_focus,
+    }
+}
+
+/// Pulls the camera back as a locked-on target gets further away
+fn framing_distance(camera_params: &ThirdPersonCamera, player_pos: Vec3, lock_target: Option<Vec3>) -> f32 {
+    let extra = lock_target.map_or(0.0, |target| (target.distance(player_pos) * 0.25).min(4.0));
+    camera_params.distance + extra
+}
+
 pub fn camera_collision_detection(
-    player_query: Query<(Entity, &Transform), (With<Player>, Without<ThirdPersonCamera>)>,
+    player_query: Query<(Entity, &Transform, Option<&LockOn>), (With<Player>, Without<ThirdPersonCamera>)>,
     mut camera_query: Query<(&mut Transform, &ThirdPersonCamera), Without<Player>>,
+    lock_targets: Query<(&GlobalTransform, &Targetable)>,
     spatial_query: SpatialQuery,
     time: Res<Time>,
 ) {
     // Get player and camera data
-    let Ok((player_entity, player_transform)) = player_query.single() else { return };
+    let Ok((player_entity, player_transform, lock_on)) = player_query.single() else { return };
     let Ok((mut camera_transform, camera_params)) = camera_query.single_mut() else { return };
 
     // Player position
     let player_position = player_transform.translation;
+    let lock_target = locked_target_focus(lock_on, &lock_targets);
 
     // ======== Calculate ideal camera position ========
     let pitch_rot = Quat::from_rotation_x(camera_params.pitch);
@@ -242,7 +237,7 @@ pub fn camera_collision_detection(
     let ideal_offset = camera_rotation * Vec3::new(
         0.0,
         camera_params.height_offset,
-        camera_params.distance
+        framing_distance(camera_params, player_position, lock_target)
     );
     let ideal_position = player_position - ideal_offset;
 
@@ -369,23 +364,41 @@ pub fn camera_collision_detection(
     );
 
     // ======== Maintain focus on player ========
-    // Always maintain the same focus point - the player position plus small offset
-    let focus_pos = player_position + Vec3::new(0.0, camera_params.height_offset * 0.5, 0.0);
+    // Same focus point as the orbit, including the lock-on target when there is one
+    let focus_pos = camera_focus(camera_params, player_position, lock_target);
 
     // Make camera look at the focus point
     camera_transform.look_at(focus_pos, Vec3::Y);
 }
 
+/// Hides and locks the cursor while playing so the mouse only turns the camera
+fn grab_cursor(mut windows: Query<&mut Window, With<PrimaryWindow>>) {
+    if let Ok(mut window) = windows.single_mut() {
+        window.cursor_options.grab_mode = CursorGrabMode::Locked;
+        window.cursor_options.visible = false;
+    }
+}
+
+/// Gives the cursor back for menus, pausing and the death screen
+fn release_cursor(mut windows: Query<&mut Window, With<PrimaryWindow>>) {
+    if let Ok(mut window) = windows.single_mut() {
+        window.cursor_options.grab_mode = CursorGrabMode::None;
+        window.cursor_options.visible = true;
+    }
+}
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(OnEnter(AppState::InGame), spawn_camera)
+            .add_systems(OnEnter(InWorld), spawn_camera)
             .add_systems(Update, (
                 third_person_camera,
                 camera_collision_detection
             ).chain().run_if(in_state(AppState::InGame)))
+            .add_systems(OnEnter(AppState::InGame), grab_cursor)
+            .add_systems(OnExit(AppState::InGame), release_cursor)
             .add_plugins(TemporalAntiAliasPlugin);
     }
 }
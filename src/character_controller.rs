@@ -1,3 +1,5 @@
+mod buffer;
+mod character;
 mod components;
 mod input;
 mod states;
@@ -6,39 +8,79 @@ mod physics;
 use avian3d::math::*;
 use bevy::prelude::*;
 use crate::game_states::AppState;
+pub use buffer::*;
+pub use character::*;
 pub use components::*;
 
-/// An event sent for a movement input action.
-#[derive(Event)]
+/// An action a character wants to perform this tick.
+/// Directions are in world space, x and z of the ground plane.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MovementAction {
     Move(Vector2, bool), // Direction vector and sprint flag
     Jump,
-    Roll(Vector2),      // Direction to roll in
+    JumpReleased,       // Jump button let go, cuts the jump short while rising
+    Roll(Vector2),      // Direction to roll in, zero for a backstep
     StartBlock,         // Start blocking
     EndBlock,           // Stop blocking
+    LightAttack,        // Quick, cheap attack
+    HeavyAttack,        // Slow, strong attack
+}
+
+/// The actions queued for a character this tick, filled by input for the player and by AI for enemies.
+/// Cleared at the end of every controller update.
+#[derive(Component, Default, Debug)]
+pub struct ActionIntents(pub Vec<MovementAction>);
+
+impl ActionIntents {
+    pub fn push(&mut self, action: MovementAction) {
+        self.0.push(action);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MovementAction> {
+        self.0.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Ordering of the controller update. Anything that drives characters adds its systems to `Intents`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControllerSet {
+    Intents,  // Fill each character's ActionIntents
+    Simulate, // Turn the intents into character state and movement
 }
 
 pub struct CharacterControllerPlugin;
 
 impl Plugin for CharacterControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<MovementAction>()
+        app.configure_sets(
+                FixedUpdate,
+                (ControllerSet::Intents, ControllerSet::Simulate)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                // Input processing
+                input::player_input.in_set(ControllerSet::Intents),
+            )
             .add_systems(
                 FixedUpdate,
                 (
-                    // Input processing
-                    input::keyboard_input,
-                    input::gamepad_input,
-
                     // State management
-                    states::update_player_states,
+                    states::apply_iframe_markers,
+                    states::update_character_states,
 
                     physics::enhanced_gravity,
                     physics::update_grounded,
                     physics::movement,
                     physics::apply_movement_damping,
-                ).run_if(in_state(AppState::InGame))
-                    .chain(),
+
+                    clear_action_intents,
+                ).chain().in_set(ControllerSet::Simulate),
             )
             // Visual tilt in Update schedule for smoother animation
             .add_systems(
@@ -49,4 +91,10 @@ impl Plugin for CharacterControllerPlugin {
                 ).run_if(in_state(AppState::InGame))
             );
     }
-}
\ No newline at end of file
+}
+
+fn clear_action_intents(mut query: Query<&mut ActionIntents>) {
+    for mut intents in &mut query {
+        intents.clear();
+    }
+}
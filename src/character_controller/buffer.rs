@@ -0,0 +1,115 @@
+use std::mem::discriminant;
+use bevy::prelude::*;
+use crate::character_controller::MovementAction;
+
+/// How long a press is remembered while the character is busy, in seconds
+pub const DEFAULT_INPUT_BUFFER_WINDOW: f32 = 0.15;
+
+/// How long a jump pressed in the air is remembered, so it fires on landing
+pub const DEFAULT_JUMP_BUFFER_WINDOW: f32 = 0.15;
+
+/// How long a press waiting on something that's sure to end is remembered, e.g. a roll
+/// pressed mid-roll waiting out the recovery and cooldown
+pub const DEFAULT_BUSY_BUFFER_WINDOW: f32 = 0.6;
+
+/// Jumps, rolls and attacks pressed while the character couldn't act on them.
+/// Each kind of action keeps only its latest press, which fires as soon as the
+/// character is able to, or is forgotten once it's older than `window`.
+/// Presses waiting on something that's sure to end are kept for `busy_window` instead.
+#[derive(Component, Debug)]
+pub struct InputBuffer {
+    pub window: f32,
+    pub jump_window: f32, // Jumps get their own window, the counterpart of coyote time
+    pub busy_window: f32,
+    entries: Vec<(MovementAction, f32)>, // Buffered action and how long ago it was pressed
+}
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_INPUT_BUFFER_WINDOW,
+            jump_window: DEFAULT_JUMP_BUFFER_WINDOW,
+            busy_window: DEFAULT_BUSY_BUFFER_WINDOW,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl InputBuffer {
+    /// Whether an action is a one-off press that can wait in the buffer, rather than something held
+    pub fn accepts(action: &MovementAction) -> bool {
+        matches!(
+            action,
+            MovementAction::Jump | MovementAction::Roll(_) | MovementAction::LightAttack | MovementAction::HeavyAttack
+        )
+    }
+
+    /// Buffers a press, replacing an older press of the same action
+    pub fn push(&mut self, action: MovementAction) {
+        self.entries.retain(|(buffered, _)| discriminant(buffered) != discriminant(&action));
+        self.entries.push((action, 0.0));
+    }
+
+    /// Removes and returns the most recently pressed action that `ready` allows
+    pub fn take_newest(&mut self, ready: impl Fn(&MovementAction) -> bool) -> Option<MovementAction> {
+        let index = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (action, _))| ready(action))
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .map(|(index, _)| index)?;
+        Some(self.entries.remove(index).0)
+    }
+
+    /// Ages every press, forgetting those older than their window. Presses `waiting` get the busy window.
+    pub fn tick(&mut self, delta: f32, waiting: impl Fn(&MovementAction) -> bool) {
+        for (_, age) in &mut self.entries {
+            *age += delta;
+        }
+        let (window, jump_window, busy_window) = (self.window, self.jump_window, self.busy_window);
+        self.entries.retain(|(action, age)| {
+            *age <= if *action == MovementAction::Jump {
+                jump_window
+            } else if waiting(action) {
+                busy_window.max(window)
+            } else {
+                window
+            }
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use avian3d::math::Vector2;
+    use super::*;
+
+    fn roll() -> MovementAction {
+        MovementAction::Roll(Vector2::ZERO)
+    }
+
+    #[test]
+    fn waiting_press_outlasts_the_window_but_not_the_busy_window() {
+        let mut buffer = InputBuffer::default();
+        buffer.push(roll());
+        buffer.tick(buffer.window + 0.05, |_| true);
+        assert_eq!(buffer.take_newest(|_| false), None);
+        assert_eq!(buffer.entries.len(), 1);
+
+        buffer.tick(buffer.busy_window, |_| true);
+        assert!(buffer.entries.is_empty());
+    }
+
+    #[test]
+    fn press_no_longer_waiting_falls_back_to_the_window() {
+        let mut buffer = InputBuffer::default();
+        buffer.push(roll());
+        buffer.tick(buffer.window + 0.05, |_| false);
+        assert_eq!(buffer.take_newest(|_| true), None);
+    }
+}
@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+use crate::character_controller::InputBuffer;
+use crate::combat::{AttackKind, AttackPhase};
+
+/// Movement, roll, block and attack state shared by every controller-driven character.
+/// The player and enemies all carry one, driven through their [`ActionIntents`](super::ActionIntents).
+#[derive(Component)]
+#[require(InputBuffer)]
+pub struct Character {
+    pub is_moving: bool,
+
+    pub movement_direction: Vec3,
+
+    // Movement speeds
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub current_speed: f32,
+    pub is_sprinting: bool,
+
+    // Roll mechanics
+    pub is_rolling: bool,
+    pub roll_speed: f32,
+    pub roll_duration: f32,
+    pub roll_cooldown: f32,
+    pub roll_timer: f32,
+    pub roll_cooldown_timer: f32,
+    pub roll_direction: Vec3,
+    pub can_roll: bool,
+    pub current_roll_speed: f32, // Roll speed after equip load, set when a roll starts
+    pub is_backstepping: bool, // Roll pressed without a direction hops backwards instead
+
+    // Dodge invulnerability and recovery
+    pub roll_elapsed: f32, // Time since the roll started, including recovery
+    pub roll_iframe_start: f32, // When invulnerability begins, relative to the roll start
+    pub roll_iframe_duration: f32,
+    pub current_roll_iframes: f32, // I-frame length of the roll in progress after equip load
+    pub roll_recovery: f32, // Time after a roll before the character can act again
+    pub roll_recovery_timer: f32,
+    pub is_recovering: bool,
+    pub backstep_speed_multiplier: f32,
+    pub backstep_duration_multiplier: f32,
+    pub iframes_active: bool, // From the roll start to the roll clip's i-frame end marker, for characters timed by animation
+
+    // Equip load (affects roll speed and length)
+    pub equip_load: f32,
+    pub max_equip_load: f32,
+
+    // Jump improvements
+    pub fall_multiplier: f32, // Increases gravity during falling
+    pub low_jump_multiplier: f32, // Increases gravity while rising with jump released, for short hops
+    pub jump_held: bool, // Jump button still down since the last jump press
+    pub is_jumping: bool, // In the air from a jump, until landing. Only jumps are cut short by releasing the button.
+    pub coyote_time: f32, // Time the character can jump after leaving a platform
+    pub coyote_timer: f32,
+    pub jump_requested: bool, // Set when a jump is ready to happen, consumed by the movement system
+
+    // Block mechanics
+    pub is_blocking: bool,
+    pub can_move_while_blocking: bool,
+    pub block_movement_penalty: f32, // Speed reduction while blocking
+    pub block_damage_reduction: f32, // Fraction of incoming damage absorbed by the guard
+
+    // Attack mechanics
+    pub is_attacking: bool,
+    pub current_attack: AttackKind,
+    pub attack_phase: AttackPhase,
+    pub attack_timer: f32, // Time since the current attack started
+
+    // I-frames follow the markers on the character's roll clip instead of timers.
+    // Set once the character's model has an animator, characters without one keep the timers.
+    pub animation_timing: bool,
+    pub marked_attacks: bool, // Hitboxes follow the attack clips' markers, for rigs that mark them, instead of the weapon's timing
+    pub root_motion: Option<Vec3>, // Velocity the roll or attack clip moves the model's root at, for models with root motion
+
+    // Added for UI
+    pub stamina: f32,
+    pub max_stamina: f32,
+    pub stamina_regen_rate: f32,
+    pub stamina_use_rate: f32,
+    pub exhausted: bool,
+    pub exhaustion_timer: f32,
+
+    // Stamina costs
+    pub roll_stamina_cost: f32,
+    pub block_stamina_cost_per_sec: f32,
+    pub block_stamina_cost_per_hit: f32,
+    pub light_attack_stamina_cost: f32,
+    pub heavy_attack_stamina_cost: f32,
+}
+
+impl Default for Character {
+    fn default() -> Self {
+        Self {
+            is_moving: false,
+            movement_direction: Vec3::new(0.0, 0.0, 0.0),
+
+            // Default movement speeds
+            walk_speed: 200.0,       // Normal walking speed (increased as requested)
+            run_speed: 350.0,        // Sprint speed
+            current_speed: 200.0,    // Start at walking speed
+            is_sprinting: false,     // Not sprinting initially
+
+            // Roll settings
+            is_rolling: false,
+            roll_speed: 1000.0,       // Fast roll speed
+            roll_duration: 0.1,      // How long the roll lasts in seconds
+            roll_cooldown: 0.5,      // Time before player can roll again
+            roll_timer: 0.0,         // Current active roll time
+            roll_cooldown_timer: 0.0, // Current cooldown timer
+            roll_direction: Vec3::ZERO,
+            can_roll: true,          // Can the character roll right now
+            current_roll_speed: 1000.0,
+            is_backstepping: false,
+
+            // Dodge settings
+            roll_elapsed: 0.0,
+            roll_iframe_start: 0.0,   // Invulnerable from the first frame
+            roll_iframe_duration: 0.2, // Covers the roll and the start of recovery
+            current_roll_iframes: 0.2,
+            roll_recovery: 0.25,     // Short window where actions are buffered
+            roll_recovery_timer: 0.0,
+            is_recovering: false,
+            backstep_speed_multiplier: 0.6,    // Backsteps are slower...
+            backstep_duration_multiplier: 0.7, // ...and shorter than rolls
+            iframes_active: false,
+
+            // Equip load
+            equip_load: 25.0,        // Medium load, the default roll
+            max_equip_load: 60.0,
+
+            // Jump improvements
+            fall_multiplier: 2.5,    // Makes falling faster than rising
+            low_jump_multiplier: 2.0, // Tapping jump gives about half the height of holding it
+            jump_held: false,
+            is_jumping: false,
+            coyote_time: 0.1,        // Short grace period for jumps
+            coyote_timer: 0.0,       // Current coyote time
+            jump_requested: false,
+
+            // Block settings
+            is_blocking: false,
+            can_move_while_blocking: true,
+            block_movement_penalty: 0.5, // Move at 50% speed while blocking
+            block_damage_reduction: 0.8, // Absorb 80% of a blocked hit
+
+            // Attack settings
+            is_attacking: false,
+            current_attack: AttackKind::Light,
+            attack_phase: AttackPhase::Idle,
+            attack_timer: 0.0,
+
+            animation_timing: false,
+            marked_attacks: false,
+            root_motion: None,
+
+            // Stats
+            stamina: 100.0,
+            max_stamina: 100.0,
+            stamina_regen_rate: 30.0,
+            stamina_use_rate: 15.0,
+            exhausted: false,
+            exhaustion_timer: 0.0,
+
+            // Stamina costs
+            roll_stamina_cost: 20.0,       // Cost per roll
+            block_stamina_cost_per_sec: 5.0, // Cost per second while blocking
+            block_stamina_cost_per_hit: 15.0, // Cost for each hit absorbed while blocking
+            light_attack_stamina_cost: 15.0,  // Cost per light attack
+            heavy_attack_stamina_cost: 30.0,  // Cost per heavy attack
+
+        }
+    }
+}
+
+/// Roll class based on how much of `max_equip_load` is carried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EquipLoadClass {
+    Light,      // Under 30%: fast, long roll
+    Medium,     // Under 70%: normal roll
+    Heavy,      // Up to 100%: slow, short roll
+    Overloaded, // Over 100%: can't roll
+}
+
+impl Character {
+    pub fn equip_load_ratio(&self) -> f32 {
+        if self.max_equip_load > 0.0 {
+            self.equip_load / self.max_equip_load
+        } else {
+            0.0
+        }
+    }
+
+    pub fn equip_load_class(&self) -> EquipLoadClass {
+        let ratio = self.equip_load_ratio();
+        if ratio < 0.3 {
+            EquipLoadClass::Light
+        } else if ratio < 0.7 {
+            EquipLoadClass::Medium
+        } else if ratio <= 1.0 {
+            EquipLoadClass::Heavy
+        } else {
+            EquipLoadClass::Overloaded
+        }
+    }
+
+    /// Speed and duration multipliers applied to rolls at the current equip load
+    pub fn roll_modifiers(&self) -> (f32, f32) {
+        match self.equip_load_class() {
+            EquipLoadClass::Light => (1.15, 1.2),
+            EquipLoadClass::Medium => (1.0, 1.0),
+            EquipLoadClass::Heavy => (0.7, 0.8),
+            EquipLoadClass::Overloaded => (0.0, 0.0),
+        }
+    }
+
+    /// Starts a roll in the already chosen `roll_direction`, its speed and length scaled by equip load and backstepping
+    pub fn start_roll(&mut self, speed_multiplier: f32, duration_multiplier: f32) {
+        self.is_rolling = true;
+        self.roll_elapsed = 0.0;
+        self.roll_timer = self.roll_duration * duration_multiplier;
+        self.current_roll_speed = self.roll_speed * speed_multiplier;
+        self.current_roll_iframes = self.roll_iframe_duration * duration_multiplier;
+        // Rolls timed by their clip are invulnerable from the first frame, until the clip's end marker
+        self.iframes_active = true;
+    }
+
+    /// Whether the current roll or backstep is inside its i-frame window
+    pub fn is_invulnerable(&self) -> bool {
+        if !(self.is_rolling || self.is_recovering) {
+            return false;
+        }
+        if self.animation_timing {
+            return self.iframes_active;
+        }
+        self.roll_elapsed >= self.roll_iframe_start
+            && self.roll_elapsed < self.roll_iframe_start + self.current_roll_iframes
+    }
+
+    pub fn attack_stamina_cost(&self, kind: AttackKind) -> f32 {
+        match kind {
+            AttackKind::Light => self.light_attack_stamina_cost,
+            AttackKind::Heavy => self.heavy_attack_stamina_cost,
+        }
+    }
+
+    /// Clears all transient action state and refills stamina, e.g. after respawning.
+    pub fn reset_state(&mut self) {
+        self.is_moving = false;
+        self.movement_direction = Vec3::ZERO;
+        self.is_sprinting = false;
+        self.current_speed = self.walk_speed;
+
+        self.is_rolling = false;
+        self.roll_timer = 0.0;
+        self.roll_cooldown_timer = 0.0;
+        self.roll_direction = Vec3::ZERO;
+        self.can_roll = true;
+        self.is_backstepping = false;
+        self.roll_elapsed = 0.0;
+        self.roll_recovery_timer = 0.0;
+        self.is_recovering = false;
+        self.iframes_active = false;
+        self.root_motion = None;
+        self.jump_requested = false;
+        self.jump_held = false;
+        self.is_jumping = false;
+
+        self.coyote_timer = 0.0;
+        self.is_blocking = false;
+
+        self.is_attacking = false;
+        self.attack_phase = AttackPhase::Idle;
+        self.attack_timer = 0.0;
+
+        self.stamina = self.max_stamina;
+        self.exhausted = false;
+        self.exhaustion_timer = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animation_timed_roll_is_invulnerable_as_it_starts() {
+        let mut character = Character { animation_timing: true, ..default() };
+        assert!(!character.is_invulnerable());
+
+        character.start_roll(1.0, 1.0);
+        assert!(character.is_invulnerable());
+    }
+
+    #[test]
+    fn timed_roll_is_invulnerable_as_it_starts() {
+        let mut character = Character::default();
+        character.start_roll(1.0, 1.0);
+        assert!(character.is_invulnerable());
+    }
+}
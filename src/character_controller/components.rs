@@ -23,6 +23,9 @@ pub struct CharacterController;
 #[component(storage = "SparseSet")]
 pub struct Grounded;
 
+/// Default [`MaxSlopeAngle`] for characters, in degrees
+pub const DEFAULT_MAX_SLOPE_DEGREES: Scalar = 30.0;
+
 /// The maximum angle a slope can have for a character controller
 /// to be able to climb and jump. If the slope is steeper than this angle,
 /// the character will slide down.
@@ -73,29 +76,35 @@ impl CharacterController {
         MaxSlopeAngle,
         GroundNormal,
     ) {
-        // Create shape caster as a slightly smaller version of collider
-        let mut caster_shape = collider.clone();
-        caster_shape.set_scale(Vector::ONE * 0.95, 10); // Smaller scale for better detection
+        let shape_caster = Self::shape_caster(&collider);
 
         (
             CharacterController,
             RigidBody::Dynamic,
             collider,
-            ShapeCaster::new(
-                caster_shape,
-                Vector::ZERO,
-                Quaternion::default(),
-                Dir3::NEG_Y,
-            )
-                .with_max_distance(0.3)  // Increased distance for better slope detection
-                .with_max_hits(5),        // More hits to find the best contact point
+            shape_caster,
             LockedAxes::ROTATION_LOCKED,
             MovementAcceleration(),
             MovementDampingFactor(0.9),
             JumpImpulse(7.0),
-            MaxSlopeAngle((30.0 as Scalar).to_radians()),
+            MaxSlopeAngle(DEFAULT_MAX_SLOPE_DEGREES.to_radians()),
             GroundNormal::new(),
         )
     }
 
+    /// Ground detection cast for a body with the given collider
+    pub fn shape_caster(collider: &Collider) -> ShapeCaster {
+        // Create shape caster as a slightly smaller version of collider
+        let mut caster_shape = collider.clone();
+        caster_shape.set_scale(Vector::ONE * 0.95, 10); // Smaller scale for better detection
+
+        ShapeCaster::new(
+            caster_shape,
+            Vector::ZERO,
+            Quaternion::default(),
+            Dir3::NEG_Y,
+        )
+            .with_max_distance(0.3)  // Increased distance for better slope detection
+            .with_max_hits(5)        // More hits to find the best contact point
+    }
 }
\ No newline at end of file
@@ -1,117 +1,76 @@
-use avian3d::math::{Scalar, Vector2};
-use bevy::input::ButtonInput;
-use bevy::prelude::{EventWriter, Gamepad, GamepadAxis, GamepadButton, KeyCode, MouseButton, Query, Res};
-use crate::character_controller::MovementAction;
+use avian3d::math::Vector2;
+use bevy::math::{EulerRot, Quat, Vec3};
+use bevy::prelude::{Query, Transform, With};
+use crate::bindings::{ActionInput, InputAction};
+use crate::camera::ThirdPersonCamera;
+use crate::character_controller::{ActionIntents, Character, MovementAction};
 use crate::player::Player;
 
-/// Sends [`MovementAction`] events based on keyboard input.
-pub fn keyboard_input(
-    mut movement_event_writer: EventWriter<MovementAction>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mouse_input: Res<ButtonInput<MouseButton>>,
-    player_query: Query<&Player>,
-) {
-    let Ok(player) = player_query.single() else { return };
-
-    // Basic movement
-    let up = keyboard_input.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]);
-    let down = keyboard_input.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]);
-    let left = keyboard_input.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]);
-    let right = keyboard_input.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]);
-
-    // Check if sprinting (any shift key)
-    let sprinting = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+// Stick tilt below which the player is considered not moving
+const MOVE_DEADZONE: f32 = 0.1;
 
-    // Calculate movement direction
-    let horizontal = right as i8 - left as i8;
-    let vertical = up as i8 - down as i8;
-    let direction = Vector2::new(horizontal as Scalar, vertical as Scalar).clamp_length_max(1.0);
+/// Turns a stick or WASD direction into a world space direction relative to the camera
+fn camera_relative(direction: Vector2, camera_transform: &Transform) -> Vector2 {
+    let camera_yaw = Quat::from_rotation_y(camera_transform.rotation.to_euler(EulerRot::YXZ).0);
+    let world = camera_yaw * Vec3::new(direction.x, 0.0, -direction.y);
+    Vector2::new(world.x, world.z)
+}
 
-    // Send movement event if there's input and not rolling
-    if direction != Vector2::ZERO && !player.is_rolling {
-        movement_event_writer.write(MovementAction::Move(direction, sprinting));
+/// Queues [`MovementAction`]s for the player from keyboard, mouse and gamepad input, through the action map.
+pub fn player_input(
+    input: ActionInput,
+    mut player_query: Query<(&Character, &mut ActionIntents), With<Player>>,
+    camera_query: Query<&Transform, With<ThirdPersonCamera>>,
+) {
+    let (Ok((player, mut intents)), Ok(camera_transform)) =
+        (player_query.single_mut(), camera_query.single()) else {
+        return;
+    };
+
+    // Basic movement, digital keys and analog sticks alike
+    let horizontal = input.value(InputAction::MoveRight) - input.value(InputAction::MoveLeft);
+    let vertical = input.value(InputAction::MoveUp) - input.value(InputAction::MoveDown);
+    let direction = Vector2::new(horizontal, vertical).clamp_length_max(1.0);
+    let direction = if direction.length() > MOVE_DEADZONE {
+        camera_relative(direction, camera_transform)
+    } else {
+        Vector2::ZERO
+    };
+
+    let sprinting = input.pressed(InputAction::Sprint);
+
+    // Send movement if there's input and not rolling or recovering from a roll
+    if direction != Vector2::ZERO && !player.is_rolling && !player.is_recovering {
+        intents.push(MovementAction::Move(direction, sprinting));
     }
 
-    // Handle jump
-    if keyboard_input.just_pressed(KeyCode::Space) && !player.is_rolling {
-        movement_event_writer.write(MovementAction::Jump);
+    // Jumps, rolls and attacks are sent even while busy, the character's input buffer holds them
+    if input.just_pressed(InputAction::Jump) {
+        intents.push(MovementAction::Jump);
+    }
+    if input.just_released(InputAction::Jump) {
+        intents.push(MovementAction::JumpReleased);
     }
 
     // Handle roll
-    if keyboard_input.just_pressed(KeyCode::ControlLeft) && player.can_roll && !player.is_rolling && !player.exhausted {
-        // Use the current movement direction for rolling, or forward if not moving
-        let roll_direction = if direction != Vector2::ZERO {
-            direction
-        } else {
-            Vector2::new(0.0, 1.0) // Default to forward
-        };
-
-        movement_event_writer.write(MovementAction::Roll(roll_direction));
+    if input.just_pressed(InputAction::Roll) {
+        // Roll in the current movement direction, or backstep if not moving
+        intents.push(MovementAction::Roll(direction));
     }
 
-    // Handle blocking (right mouse button)
-    if mouse_input.just_pressed(MouseButton::Right) && !player.is_rolling {
-        movement_event_writer.write(MovementAction::StartBlock);
+    // Handle blocking while held
+    if input.just_pressed(InputAction::Block) && !player.is_rolling {
+        intents.push(MovementAction::StartBlock);
     }
-    if mouse_input.just_released(MouseButton::Right) && player.is_blocking {
-        movement_event_writer.write(MovementAction::EndBlock);
+    if input.just_released(InputAction::Block) && player.is_blocking {
+        intents.push(MovementAction::EndBlock);
     }
-}
-
-/// Sends [`MovementAction`] events based on gamepad input.
-pub fn gamepad_input(
-    mut movement_event_writer: EventWriter<MovementAction>,
-    gamepads: Query<&Gamepad>,
-    player_query: Query<&Player>,
-) {
-    let Ok(player) = player_query.single() else { return };
 
-    for gamepad in gamepads.iter() {
-        // Movement with left stick
-        if let (Some(x), Some(y)) = (
-            gamepad.get(GamepadAxis::LeftStickX),
-            gamepad.get(GamepadAxis::LeftStickY),
-        ) {
-            // Use Right Trigger or Right Shoulder for sprinting in gamepad
-            let sprint = gamepad.pressed(GamepadButton::RightTrigger2) ||
-                gamepad.pressed(GamepadButton::RightTrigger2);
-
-            let direction = Vector2::new(x as Scalar, y as Scalar).clamp_length_max(1.0);
-
-            // Only send movement if not rolling
-            if direction.length_squared() > 0.01 && !player.is_rolling {
-                movement_event_writer.write(MovementAction::Move(direction, sprint));
-            }
-        }
-
-        // Jump (A/Cross button)
-        if gamepad.just_pressed(GamepadButton::South) && !player.is_rolling {
-            movement_event_writer.write(MovementAction::Jump);
-        }
-
-        // Roll (B/Circle button)
-        if gamepad.just_pressed(GamepadButton::East) && player.can_roll && !player.is_rolling && !player.exhausted {
-            // Get current direction from left stick
-            let x = gamepad.get(GamepadAxis::LeftStickX).unwrap_or(0.0);
-            let y = gamepad.get(GamepadAxis::LeftStickY).unwrap_or(0.0);
-            let direction = Vector2::new(x as Scalar, y as Scalar);
-
-            // Use current direction, or forward if stick is neutral
-            let roll_direction = if direction.length_squared() > 0.01 {
-                direction.clamp_length_max(1.0)
-            } else {
-                Vector2::new(0.0, 1.0) // Default to forward
-            };
-
-            movement_event_writer.write(MovementAction::Roll(roll_direction));
-        }
-
-        // Block with R2/Right Trigger
-        if gamepad.just_pressed(GamepadButton::RightTrigger) && !player.is_rolling {
-            movement_event_writer.write(MovementAction::StartBlock);
-        }
-        if gamepad.just_released(GamepadButton::RightTrigger) && player.is_blocking {
-            movement_event_writer.write(MovementAction::EndBlock);
-        }
+    // Handle attacks
+    if input.just_pressed(InputAction::LightAttack) {
+        intents.push(MovementAction::LightAttack);
     }
-}
\ No newline at end of file
+    if input.just_pressed(InputAction::HeavyAttack) {
+        intents.push(MovementAction::HeavyAttack);
+    }
+}
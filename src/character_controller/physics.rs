@@ -3,28 +3,26 @@ use avian3d::position::Rotation;
 use avian3d::prelude::{GravityScale, LinearVelocity, ShapeHits};
 use bevy::color::Color;
 use bevy::math::{EulerRot, Quat, Vec3};
-use bevy::prelude::{Commands, Entity, EventReader, Gizmos, ParamSet, Query, Res, Time, Transform, With};
-use crate::camera::ThirdPersonCamera;
+use bevy::prelude::{Commands, Entity, Gizmos, GlobalTransform, Query, Res, Time, Transform, With};
 use crate::character_controller::components::*;
-use crate::character_controller::MovementAction;
+use crate::character_controller::{ActionIntents, Character, MovementAction};
+use crate::lock_on::{locked_target_focus, LockOn, Targetable};
 use crate::player::Player;
 
 /// Custom gravity system for improved jump feel
 pub fn enhanced_gravity(
-    mut player_query: Query<(&Player, &mut GravityScale)>,
-    mut linear_velocity_query: Query<&mut LinearVelocity, With<Player>>,
+    mut query: Query<(&Character, &mut GravityScale, &LinearVelocity)>,
 ) {
-    if let (Ok((player, mut gravity_scale)), Ok(linear_velocity)) =
-        (player_query.single_mut(), linear_velocity_query.single_mut()) {
-
+    for (character, mut gravity_scale, linear_velocity) in &mut query {
         // If we're falling, increase gravity
         if linear_velocity.y < 0.0 {
             // Apply fall multiplier for faster descent
-            gravity_scale.0 = 2.0 * player.fall_multiplier;
+            gravity_scale.0 = 2.0 * character.fall_multiplier;
         }
-        // If we're rising but jump button was released, apply low jump multiplier
-        else if linear_velocity.y > 0.0 {
-            gravity_scale.0 = 2.0;
+        // If we're rising from a jump but the button was released, apply low jump multiplier.
+        // Walking uphill or being knocked upwards keeps normal gravity.
+        else if linear_velocity.y > 0.0 && character.is_jumping && !character.jump_held {
+            gravity_scale.0 = 2.0 * character.low_jump_multiplier;
         }
         else {
             // Default gravity scale
@@ -33,155 +31,171 @@ pub fn enhanced_gravity(
     }
 }
 
+/// Speed multiplier for moving along `direction` on the ground under the character, slower uphill and faster downhill
+fn slope_factor(direction: Vec3, grounded: bool, ground_normal: Option<&GroundNormal>) -> f32 {
+    let Some(ground_normal) = ground_normal.filter(|_| grounded) else {
+        return 1.0;
+    };
+    let normal = ground_normal.normal();
+
+    // Only adjust for non-vertical slopes
+    if (normal - Vector::Y).length_squared() <= 0.001 {
+        return 1.0;
+    }
+
+    // Calculate slope dot product
+    let slope_dot = direction.normalize_or_zero().dot(Vec3::new(normal.x, 0.0, normal.z).normalize());
+
+    if slope_dot < 0.0 {
+        // Uphill - slowed down
+        1.0 - slope_dot.abs() * 0.4
+    } else {
+        // Downhill - speed up
+        1.0 + slope_dot * 0.3
+    }
+}
+
 /// Handles movement including rolling state
 pub fn movement(
     time: Res<Time>,
-    mut movement_event_reader: EventReader<MovementAction>,
-    mut player_camera_set: ParamSet<(
-        Query<&Transform, With<ThirdPersonCamera>>,
-        Query<(&mut Player, &mut Transform)>,
-    )>,
-    mut controllers: Query<(
-        &MovementAcceleration,
+    mut characters: Query<(
+        &mut Character,
+        &mut Transform,
+        &ActionIntents,
         &JumpImpulse,
         &mut LinearVelocity,
-        Entity,
         Option<&GroundNormal>,
         Option<&Grounded>,
+        Option<&LockOn>,
     ), With<CharacterController>>,
+    lock_targets: Query<(&GlobalTransform, &Targetable)>,
 ) {
     let delta_time = time.delta_secs_f64().adjust_precision();
 
-    // Get camera transform first
-    let camera_transform = {
-        let camera_query = player_camera_set.p0();
-        if let Ok(transform) = camera_query.single() {
-            *transform
-        } else {
-            return;
-        }
-    };
+    for (mut character, mut transform, intents, jump_impulse, mut linear_velocity, ground_normal, grounded, lock_on) in &mut characters {
+        // While locked on the character strafes, facing the target instead of the move direction.
+        // Sprinting breaks the strafe so the character can run freely.
+        let strafe_focus = locked_target_focus(lock_on, &lock_targets)
+            .filter(|_| !character.is_sprinting);
 
-    // Extract the camera's yaw rotation
-    let camera_yaw = Quat::from_rotation_y(camera_transform.rotation.to_euler(EulerRot::YXZ).0);
+        // A jump released from the input buffer this tick, only good for this tick
+        let jump_requested = std::mem::take(&mut character.jump_requested);
 
-    // Now get the player query
-    let mut player_query = player_camera_set.p1();
-    let (mut player, mut player_transform) = player_query.single_mut().expect("No player found");
+        // Coyote time is topped up while standing on the ground and runs out after leaving it
+        if grounded.is_some() && linear_velocity.y <= 0.0 {
+            character.coyote_timer = character.coyote_time;
+            character.is_jumping = false;
+        } else {
+            character.coyote_timer = (character.coyote_timer - time.delta_secs()).max(0.0);
+        }
 
-    // Handle rolling motion if player is rolling
-    if player.is_rolling {
-        for (_, _, mut linear_velocity, _, _, _) in &mut controllers {
-            // Apply roll velocity
-            let roll_velocity = player.roll_direction * player.roll_speed * delta_time;
+        // Handle rolling motion if the character is rolling
+        if character.is_rolling {
+            // Apply roll velocity. A clip with root motion moves the character as authored, up and
+            // down slopes like walking does.
+            let roll_velocity = match character.root_motion {
+                Some(velocity) => velocity * slope_factor(velocity, grounded.is_some(), ground_normal),
+                None => character.roll_direction * character.current_roll_speed * delta_time,
+            };
             linear_velocity.x = roll_velocity.x;
             linear_velocity.z = roll_velocity.z;
+
+            // Face the roll direction, backsteps keep facing forward
+            if !character.is_backstepping && character.roll_direction.length_squared() > 0.0 {
+                transform.rotation = Quat::from_rotation_y(
+                    f32::atan2(character.roll_direction.x, character.roll_direction.z)
+                );
+            }
+            continue;
+        }
+
+        // No control during roll recovery, damping brings the character to a stop
+        if character.is_recovering {
+            continue;
         }
-        return;
-    }
 
-    // If blocking and can't move while blocking, zero velocity and return
-    if player.is_blocking && !player.can_move_while_blocking {
-        for (_, _, mut linear_velocity, _, _, _) in &mut controllers {
+        // Attacks plant the character in place until they finish, unless their clip moves it
+        if character.is_attacking {
+            let attack_velocity = character.root_motion.map_or(Vec3::ZERO, |velocity| {
+                velocity * slope_factor(velocity, grounded.is_some(), ground_normal)
+            });
+            linear_velocity.x = attack_velocity.x;
+            linear_velocity.z = attack_velocity.z;
+            continue;
+        }
+
+        // If blocking and can't move while blocking, zero velocity and skip
+        if character.is_blocking && !character.can_move_while_blocking {
             linear_velocity.x = 0.0;
             linear_velocity.z = 0.0;
+            continue;
         }
-        return;
-    }
 
-    // Normal movement processing
-    for event in movement_event_reader.read() {
-        for (_, jump_impulse, mut linear_velocity, _, ground_normal, grounded) in &mut controllers {
-            match event {
-                MovementAction::Move(movement, _) => {
-                    if movement.length_squared() > 0.0 {
-                        // Convert input direction
-                        let movement_local = Vec3::new(movement.x, 0.0, -movement.y);
-                        let movement_world = camera_yaw * movement_local;
-
-                        // Store normalized direction
-                        player.movement_direction = movement_world.normalize();
-
-                        // Apply slope adjustments if on ground
-                        if grounded.is_some() && ground_normal.is_some() {
-                            let normal = ground_normal.unwrap().normal();
-
-                            // Only adjust for non-vertical slopes
-                            if (normal - Vector::Y).length_squared() > 0.001 {
-                                // Calculate slope dot product
-                                let slope_dot = movement_world.normalize().dot(Vec3::new(normal.x, 0.0, normal.z).normalize());
-
-                                // Calculate slope factor
-                                let slope_factor = if slope_dot < 0.0 {
-                                    // Uphill - slowed down
-                                    1.0 - slope_dot.abs() * 0.4
-                                } else {
-                                    // Downhill - speed up
-                                    1.0 + slope_dot * 0.3
-                                };
-
-                                // Apply slope-adjusted velocity
-                                linear_velocity.x = movement_world.x * player.current_speed * delta_time * slope_factor;
-                                linear_velocity.z = movement_world.z * player.current_speed * delta_time * slope_factor;
-                            } else {
-                                // Normal movement on flat ground
-                                linear_velocity.x = movement_world.x * player.current_speed * delta_time;
-                                linear_velocity.z = movement_world.z * player.current_speed * delta_time;
-                            }
-                        } else {
-                            // Regular movement in air
-                            linear_velocity.x = movement_world.x * player.current_speed * delta_time;
-                            linear_velocity.z = movement_world.z * player.current_speed * delta_time;
-                        }
+        // Normal movement processing
+        for event in intents.iter() {
+            if let MovementAction::Move(movement, _) = event {
+                if movement.length_squared() > 0.0 {
+                    // Directions are already in world space
+                    let movement_world = Vec3::new(movement.x, 0.0, movement.y);
 
-                        // Rotate player to face movement direction
+                    // Store normalized direction
+                    character.movement_direction = movement_world.normalize();
+
+                    // Apply slope-adjusted velocity, flat ground and air leave it as is
+                    let slope = slope_factor(movement_world, grounded.is_some(), ground_normal);
+                    linear_velocity.x = movement_world.x * character.current_speed * delta_time * slope;
+                    linear_velocity.z = movement_world.z * character.current_speed * delta_time * slope;
+
+                    // Rotate to face movement direction, strafing faces the target below
+                    if strafe_focus.is_none() {
                         let target_rotation = Quat::from_rotation_y(
                             f32::atan2(movement_world.x, movement_world.z)
                         );
 
                         // Smoothly interpolate rotation
-                        player_transform.rotation = player_transform.rotation.slerp(
+                        transform.rotation = transform.rotation.slerp(
                             target_rotation,
                             10.0 * time.delta_secs()
                         );
                     }
                 }
-                MovementAction::Jump => {
-                    // Allow jump if grounded OR within coyote time
-                    let can_jump = grounded.is_some() || player.coyote_timer > 0.0;
+            }
+        }
 
-                    if can_jump {
-                        // Apply jump force - simplified for reliability
-                        linear_velocity.y = jump_impulse.0;
+        if jump_requested {
+            // Allow jump if grounded OR within coyote time
+            let can_jump = grounded.is_some() || character.coyote_timer > 0.0;
 
-                        // If on ground and we have a normal, add some directional impulse
-                        if grounded.is_some() && ground_normal.is_some() {
-                            let normal = ground_normal.unwrap().normal();
+            if can_jump {
+                // Apply jump force - simplified for reliability
+                linear_velocity.y = jump_impulse.0;
 
-                            // Add a small horizontal component based on ground normal
-                            linear_velocity.x += normal.x * jump_impulse.0 * 0.3;
-                            linear_velocity.z += normal.z * jump_impulse.0 * 0.3;
-                        }
+                // If on ground and we have a normal, add some directional impulse
+                if grounded.is_some() && ground_normal.is_some() {
+                    let normal = ground_normal.unwrap().normal();
 
-                        // Reset coyote timer
-                        player.coyote_timer = 0.0;
-                    }
+                    // Add a small horizontal component based on ground normal
+                    linear_velocity.x += normal.x * jump_impulse.0 * 0.3;
+                    linear_velocity.z += normal.z * jump_impulse.0 * 0.3;
                 }
-                _ => {}
+
+                // Reset coyote timer
+                character.coyote_timer = 0.0;
+                character.is_jumping = true;
             }
         }
-    }
 
-    // Update coyote timer based on grounded state
-    let is_player_grounded = controllers.iter().any(|(_, _, _, _, _, grounded)| grounded.is_some());
-
-    if !is_player_grounded && player.coyote_timer <= 0.0 {
-        // Just left the ground, start coyote timer
-        player.coyote_timer = player.coyote_time;
-    } else if !is_player_grounded {
-        // In air, count down coyote timer
-        player.coyote_timer -= time.delta_secs();
-        player.coyote_timer = player.coyote_timer.max(0.0);
+        // Keep facing the locked-on target, whether moving or standing still
+        if let Some(focus) = strafe_focus {
+            let to_target = focus - transform.translation;
+            if to_target.x != 0.0 || to_target.z != 0.0 {
+                let target_rotation = Quat::from_rotation_y(f32::atan2(to_target.x, to_target.z));
+                transform.rotation = transform.rotation.slerp(
+                    target_rotation,
+                    10.0 * time.delta_secs()
+                );
+            }
+        }
     }
 }
 
@@ -342,23 +356,16 @@ pub fn update_character_visual_tilt(
 
 /// Slows down movement in the XZ plane when no input is given
 pub fn apply_movement_damping(
-    mut event_reader: EventReader<MovementAction>,
-    mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>
+    mut query: Query<(&MovementDampingFactor, &mut LinearVelocity, &Character, &ActionIntents)>
 ) {
-    // Check if any movement occurred this frame
-    let mut moving = false;
-    for event in event_reader.read() {
-        if let MovementAction::Move(dir, _) = event {
-            if dir.length_squared() > 0.0 {
-                moving = true;
-                break;
-            }
-        }
-    }
-
-    // Only apply damping if not actively moving
-    if !moving {
-        for (damping_factor, mut linear_velocity) in &mut query {
+    for (damping_factor, mut linear_velocity, character, intents) in &mut query {
+        // Check if this character moved this tick, move intents are ignored during roll recovery
+        let moving = !character.is_recovering && intents.iter().any(|event| {
+            matches!(event, MovementAction::Move(dir, _) if dir.length_squared() > 0.0)
+        });
+
+        // Only apply damping if not actively moving
+        if !moving {
             // We could use `LinearDamping`, but we don't want to dampen movement along the Y axis
             linear_velocity.x *= damping_factor.0;
             linear_velocity.z *= damping_factor.0;
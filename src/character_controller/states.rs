@@ -1,158 +1,248 @@
 use avian3d::math::Vector2;
-use bevy::math::{EulerRot, Quat, Vec3};
-use bevy::prelude::{EventReader, Query, Res, Time, Transform, With, Without};
-use crate::camera::ThirdPersonCamera;
-use crate::character_controller::MovementAction;
-use crate::player::Player;
-// Enhanced system to update player states including roll and block
-pub fn update_player_states(
-    time: Res<Time>,
-    mut movement_events: EventReader<MovementAction>,
-    mut player_query: Query<(&mut Player, &Transform)>,
-    camera_query: Query<&Transform, (With<ThirdPersonCamera>, Without<Player>)>,
+use bevy::math::Vec3;
+use bevy::prelude::{EventReader, Has, Query, Res, Time, Transform};
+use crate::animation::RollIFrameEnd;
+use crate::character_controller::{ActionIntents, Character, EquipLoadClass, Grounded, InputBuffer, MovementAction};
+use crate::combat::{AttackKind, AttackPhase};
+
+// Closes the i-frames of characters whose rolls are timed by their roll clip
+pub fn apply_iframe_markers(
+    mut iframe_end: EventReader<RollIFrameEnd>,
+    mut characters: Query<&mut Character>,
 ) {
-    let (Ok((mut player, _player_transform)), Ok(camera_transform)) =
-        (player_query.single_mut(), camera_query.single()) else {
-        return;
-    };
+    for event in iframe_end.read() {
+        if let Ok(mut character) = characters.get_mut(event.character) {
+            character.iframes_active = false;
+        }
+    }
+}
 
+// Updates roll, block, attack, sprint and stamina state for every character from its intents
+pub fn update_character_states(
+    time: Res<Time>,
+    mut characters: Query<(&mut Character, &mut InputBuffer, &Transform, &ActionIntents, Has<Grounded>)>,
+) {
     let delta = time.delta_secs();
 
-    // Default to not moving/sprinting unless we see a Move event
-    player.is_moving = false;
-    let mut sprint_requested = false;
-    let mut roll_requested = false;
-    let mut roll_direction = Vector2::ZERO;
-    let mut block_start_requested = false;
-    let mut block_end_requested = false;
-
-    // Process all movement events for this frame
-    for event in movement_events.read() {
-        match event {
-            MovementAction::Move(direction, sprinting) => {
-                if direction.length_squared() > 0.0 {
-                    player.is_moving = true;
-                    // Only consider sprinting if movement keys are pressed
-                    if *sprinting {
-                        sprint_requested = true;
-                    }
+    for (mut character, mut buffer, transform, intents, grounded) in &mut characters {
+        // Default to not moving/sprinting unless we see a Move event
+        character.is_moving = false;
+        let mut sprint_requested = false;
+        let mut roll_requested = false;
+        let mut roll_direction = Vector2::ZERO;
+        let mut block_start_requested = false;
+        let mut block_end_requested = false;
+        let mut attack_requested = None;
+
+        // Process all actions queued for this tick
+        for event in intents.iter() {
+            // Jumps, rolls and attacks go through the buffer, so presses made while busy aren't lost
+            if InputBuffer::accepts(event) {
+                if *event == MovementAction::Jump {
+                    character.jump_held = true;
                 }
-            },
-            MovementAction::Roll(direction) => {
-                roll_requested = true;
-                roll_direction = *direction;
-            },
-            MovementAction::StartBlock => {
-                block_start_requested = true;
-            },
-            MovementAction::EndBlock => {
-                block_end_requested = true;
-            },
-            _ => {}
+                buffer.push(*event);
+                continue;
+            }
+
+            match event {
+                MovementAction::Move(direction, sprinting) => {
+                    if direction.length_squared() > 0.0 {
+                        character.is_moving = true;
+                        // Only consider sprinting if movement keys are pressed
+                        if *sprinting {
+                            sprint_requested = true;
+                        }
+                    }
+                },
+                MovementAction::StartBlock => {
+                    block_start_requested = true;
+                },
+                MovementAction::EndBlock => {
+                    block_end_requested = true;
+                },
+                MovementAction::JumpReleased => {
+                    character.jump_held = false;
+                },
+                _ => {}
+            }
         }
-    }
 
-    // Handle roll state and timer
-    if player.is_rolling {
-        player.roll_timer -= delta;
-        if player.roll_timer <= 0.0 {
-            // Roll finished
-            player.is_rolling = false;
-            player.roll_timer = 0.0;
-            // Start cooldown
-            player.roll_cooldown_timer = player.roll_cooldown;
-            player.can_roll = false;
+        // Handle roll state and timer
+        if character.is_rolling {
+            character.roll_elapsed += delta;
+            character.roll_timer -= delta;
+            if character.roll_timer <= 0.0 {
+                // Roll finished, move into recovery
+                character.is_rolling = false;
+                character.is_backstepping = false;
+                character.roll_timer = 0.0;
+                character.is_recovering = true;
+                character.roll_recovery_timer = character.roll_recovery;
+                // Start cooldown
+                character.roll_cooldown_timer = character.roll_cooldown;
+                character.can_roll = false;
+            }
+        } else if character.is_recovering {
+            // I-frames can extend into recovery, so keep counting
+            character.roll_elapsed += delta;
+            character.roll_recovery_timer -= delta;
+            if character.roll_recovery_timer <= 0.0 {
+                character.is_recovering = false;
+                character.roll_recovery_timer = 0.0;
+                // In case the roll clip was cut off before its end marker
+                character.iframes_active = false;
+            }
         }
-    } else if !player.can_roll {
-        // Handle roll cooldown
-        player.roll_cooldown_timer -= delta;
-        if player.roll_cooldown_timer <= 0.0 {
-            player.can_roll = true;
-            player.roll_cooldown_timer = 0.0;
+
+        if !character.is_rolling && !character.can_roll {
+            // Handle roll cooldown
+            character.roll_cooldown_timer -= delta;
+            if character.roll_cooldown_timer <= 0.0 {
+                character.can_roll = true;
+                character.roll_cooldown_timer = 0.0;
+            }
         }
-    }
 
-    // Process new roll request if player can roll and has stamina
-    if roll_requested && player.can_roll && !player.is_rolling && !player.exhausted && player.stamina >= player.roll_stamina_cost {
-        // Start rolling
-        player.is_rolling = true;
-        player.roll_timer = player.roll_duration;
-
-        // Convert input direction to world space using camera orientation
-        let camera_yaw = Quat::from_rotation_y(camera_transform.rotation.to_euler(EulerRot::YXZ).0);
-        let local_direction = Vec3::new(roll_direction.x, 0.0, -roll_direction.y);
-        player.roll_direction = camera_yaw * local_direction;
-
-        // Consume stamina
-        player.stamina -= player.roll_stamina_cost;
-        if player.stamina < 0.0 {
-            player.stamina = 0.0;
+        // Fire the most recent buffered press the character is now able to act on
+        let free = !character.is_rolling && !character.is_recovering && !character.is_attacking;
+        let can_roll = free && character.can_roll && !character.exhausted
+            && character.stamina >= character.roll_stamina_cost
+            && character.equip_load_class() != EquipLoadClass::Overloaded;
+        let can_attack = |kind| free && !character.exhausted && character.stamina >= character.attack_stamina_cost(kind);
+        let can_light_attack = can_attack(AttackKind::Light);
+        let can_heavy_attack = can_attack(AttackKind::Heavy);
+        let can_jump = free && (grounded || character.coyote_timer > 0.0);
+
+        let ready = buffer.take_newest(|action| match action {
+            MovementAction::Roll(_) => can_roll,
+            MovementAction::LightAttack => can_light_attack,
+            MovementAction::HeavyAttack => can_heavy_attack,
+            MovementAction::Jump => can_jump,
+            _ => false,
+        });
+        match ready {
+            Some(MovementAction::Roll(direction)) => {
+                roll_requested = true;
+                roll_direction = direction;
+            }
+            Some(MovementAction::LightAttack) => attack_requested = Some(AttackKind::Light),
+            Some(MovementAction::HeavyAttack) => attack_requested = Some(AttackKind::Heavy),
+            Some(MovementAction::Jump) => character.jump_requested = true,
+            _ => {}
         }
+        // A roll pressed during the last one can wait out its recovery and cooldown
+        let roll_busy = character.is_rolling || character.is_recovering || !character.can_roll;
+        buffer.tick(delta, |action| roll_busy && matches!(action, MovementAction::Roll(_)));
+
+        // Process new roll request if the character can roll and has stamina
+        if roll_requested && character.can_roll && !character.is_rolling && !character.is_recovering && !character.is_attacking
+            && !character.exhausted && character.stamina >= character.roll_stamina_cost
+            && character.equip_load_class() != EquipLoadClass::Overloaded {
+            let (mut speed_multiplier, mut duration_multiplier) = character.roll_modifiers();
+
+            if roll_direction == Vector2::ZERO {
+                // No direction held: hop backwards away from where the character faces (model faces +Z)
+                let facing = transform.rotation * Vec3::Z;
+                character.roll_direction = -Vec3::new(facing.x, 0.0, facing.z).normalize_or_zero();
+                character.is_backstepping = true;
+                speed_multiplier *= character.backstep_speed_multiplier;
+                duration_multiplier *= character.backstep_duration_multiplier;
+            } else {
+                // Roll directions are already in world space
+                character.roll_direction = Vec3::new(roll_direction.x, 0.0, roll_direction.y);
+                character.is_backstepping = false;
+            }
 
-        // End blocking if player was blocking
-        player.is_blocking = false;
-    }
+            character.start_roll(speed_multiplier, duration_multiplier);
 
-    // Handle blocking state changes
-    if block_start_requested && !player.is_rolling && !player.exhausted {
-        player.is_blocking = true;
-    }
+            // Consume stamina
+            character.stamina -= character.roll_stamina_cost;
+            if character.stamina < 0.0 {
+                character.stamina = 0.0;
+            }
 
-    if block_end_requested || player.is_rolling {
-        player.is_blocking = false;
-    }
+            // End blocking if the character was blocking
+            character.is_blocking = false;
+        }
 
-    // Apply stamina cost for blocking
-    if player.is_blocking {
-        player.stamina -= player.block_stamina_cost_per_sec * delta;
+        // Process new attack request if the character is free to act and has stamina
+        if let Some(kind) = attack_requested {
+            let stamina_cost = character.attack_stamina_cost(kind);
+            if !character.is_rolling && !character.is_recovering && !character.is_attacking && !character.exhausted && character.stamina >= stamina_cost {
+                // Start the attack, phases are advanced by the combat systems
+                character.is_attacking = true;
+                character.current_attack = kind;
+                character.attack_phase = AttackPhase::Startup;
+                character.attack_timer = 0.0;
+
+                // Consume stamina
+                character.stamina -= stamina_cost;
+                if character.stamina < 0.0 {
+                    character.stamina = 0.0;
+                }
 
-        // Stop blocking if stamina depletes
-        if player.stamina <= 0.0 {
-            player.stamina = 0.0;
-            player.exhausted = true;
-            player.exhaustion_timer = 1.0;
-            player.is_blocking = false;
+                // Can't hold a guard up while swinging
+                character.is_blocking = false;
+            }
         }
-    }
 
-    // Handle sprinting state and stamina
-    if !player.is_rolling && !player.is_blocking && sprint_requested && !player.exhausted && player.stamina > 0.0 {
-        // Player wants to sprint and has stamina
-        player.is_sprinting = true;
-        player.current_speed = player.run_speed;
-
-        // Reduce stamina while sprinting
-        player.stamina -= player.stamina_use_rate * delta;
-        if player.stamina <= 0.0 {
-            player.stamina = 0.0;
-            player.exhausted = true;
-            player.exhaustion_timer = 1.0; // 1 second cooldown before regen
+        // Handle blocking state changes
+        if block_start_requested && !character.is_rolling && !character.is_attacking && !character.exhausted {
+            character.is_blocking = true;
         }
-    } else if !player.is_rolling {
-        // Set speed based on blocking state
-        player.is_sprinting = false;
-        if player.is_blocking && player.can_move_while_blocking {
-            player.current_speed = player.walk_speed * player.block_movement_penalty;
-        } else if !player.is_blocking {
-            player.current_speed = player.walk_speed;
+
+        if block_end_requested || character.is_rolling || character.is_attacking {
+            character.is_blocking = false;
         }
 
-        // Handle stamina regeneration when not using stamina abilities
-        if player.exhausted {
-            // Count down exhaust timer when exhausted
-            player.exhaustion_timer -= delta;
-            if player.exhaustion_timer <= 0.0 {
-                player.exhausted = false;
+        // Apply stamina cost for blocking
+        if character.is_blocking {
+            character.stamina -= character.block_stamina_cost_per_sec * delta;
+
+            // Stop blocking if stamina depletes
+            if character.stamina <= 0.0 {
+                character.stamina = 0.0;
+                character.exhausted = true;
+                character.exhaustion_timer = 1.0;
+                character.is_blocking = false;
             }
-        } else if !sprint_requested && !player.is_rolling && !player.is_blocking && player.stamina < player.max_stamina {
-            // Regenerate stamina when not using stamina
-            player.stamina += player.stamina_regen_rate * delta;
-            player.stamina = player.stamina.min(player.max_stamina);
         }
-    }
 
-    // Handle coyote time for jump improvements
-    if player.coyote_timer > 0.0 {
-        player.coyote_timer -= delta;
+        // Handle sprinting state and stamina
+        if !character.is_rolling && !character.is_attacking && !character.is_blocking && sprint_requested && !character.exhausted && character.stamina > 0.0 {
+            // Character wants to sprint and has stamina
+            character.is_sprinting = true;
+            character.current_speed = character.run_speed;
+
+            // Reduce stamina while sprinting
+            character.stamina -= character.stamina_use_rate * delta;
+            if character.stamina <= 0.0 {
+                character.stamina = 0.0;
+                character.exhausted = true;
+                character.exhaustion_timer = 1.0; // 1 second cooldown before regen
+            }
+        } else if !character.is_rolling {
+            // Set speed based on blocking state
+            character.is_sprinting = false;
+            if character.is_blocking && character.can_move_while_blocking {
+                character.current_speed = character.walk_speed * character.block_movement_penalty;
+            } else if !character.is_blocking {
+                character.current_speed = character.walk_speed;
+            }
+
+            // Handle stamina regeneration when not using stamina abilities
+            if character.exhausted {
+                // Count down exhaust timer when exhausted
+                character.exhaustion_timer -= delta;
+                if character.exhaustion_timer <= 0.0 {
+                    character.exhausted = false;
+                }
+            } else if !sprint_requested && !character.is_rolling && !character.is_attacking && !character.is_blocking && character.stamina < character.max_stamina {
+                // Regenerate stamina when not using stamina
+                character.stamina += character.stamina_regen_rate * delta;
+                character.stamina = character.stamina.min(character.max_stamina);
+            }
+        }
     }
-}
\ No newline at end of file
+}
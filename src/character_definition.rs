@@ -0,0 +1,206 @@
+use avian3d::prelude::Collider;
+use bevy::animation::AnimationTargetId;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::animation::{Foot, LocomotionState};
+use crate::character_controller::{CharacterController, DEFAULT_MAX_SLOPE_DEGREES};
+use crate::config::RonFileError;
+use crate::game_states::InWorld;
+use crate::navigation::NavAgent;
+
+pub struct CharacterDefinitionPlugin;
+
+impl Plugin for CharacterDefinitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<CharacterDefinition>()
+            .init_asset_loader::<CharacterDefinitionLoader>()
+            .add_systems(Update, spawn_character_models.run_if(in_state(InWorld)));
+    }
+}
+
+/// Everything that differs between character rigs, read from a `.character.ron` file under `assets/characters`.
+/// Bone paths are the names of the bones from the model's root down, joined by `/`.
+#[derive(Asset, Reflect, Debug, Clone, Deserialize)]
+pub struct CharacterDefinition {
+    pub model: String, // glTF file, relative to `assets`
+    pub scale: f32,
+    // Body capsule before scaling
+    pub capsule_radius: f32,
+    pub capsule_length: f32,
+    pub skeleton: SkeletonPaths,
+    pub clips: StateClips,
+    pub markers: ClipMarkers,
+}
+
+/// Bones the animation code needs to find in the model
+#[derive(Reflect, Debug, Clone, Deserialize)]
+pub struct SkeletonPaths {
+    pub hips: String,
+    pub legs: Vec<LegPaths>,
+    pub mask_groups: Vec<MaskGroupPath>,
+    pub upper_body: Vec<String>, // Names of the mask groups block and attack clips play on, the rest keep moving with the gait
+    // Bone whose horizontal movement in the roll and attack clips moves the character instead of the model.
+    // Left outside the mask groups, so attacks on the upper-body layer still move it.
+    pub root_motion: Option<String>,
+}
+
+/// A leg from the joint at the body down to the foot. The foot IK turns `upper` and `lower` to plant
+/// `end`, the bone footsteps are placed at. Bones between `lower` and `end` are left as animated.
+#[derive(Reflect, Debug, Clone, Deserialize)]
+pub struct LegPaths {
+    pub foot: Foot,
+    pub upper: String,
+    pub lower: String,
+    pub end: String,
+}
+
+/// A named set of bones that a clip can be switched off for. The group is the chain from `prefix`
+/// through each bone of `suffix`, so "A/B" with suffix "C/D" holds "A/B", "A/B/C" and "A/B/C/D".
+#[derive(Reflect, Debug, Clone, Deserialize)]
+pub struct MaskGroupPath {
+    pub name: String,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+/// Index of the glTF animation each locomotion state plays
+#[derive(Reflect, Debug, Clone, Deserialize)]
+pub struct StateClips {
+    pub idle: usize,
+    pub walk: usize,
+    pub run: usize,
+    pub roll: usize,
+    pub block: usize,
+    pub airborne: usize,
+    pub light_attack: usize,
+    pub heavy_attack: usize,
+}
+
+impl StateClips {
+    pub fn get(&self, state: LocomotionState) -> usize {
+        match state {
+            LocomotionState::Idle => self.idle,
+            LocomotionState::Walk => self.walk,
+            LocomotionState::Run => self.run,
+            LocomotionState::Roll => self.roll,
+            LocomotionState::Block => self.block,
+            LocomotionState::Airborne => self.airborne,
+            LocomotionState::LightAttack => self.light_attack,
+            LocomotionState::HeavyAttack => self.heavy_attack,
+        }
+    }
+}
+
+/// When things happen in the clips, in seconds into the clip
+#[derive(Reflect, Debug, Clone, Deserialize)]
+pub struct ClipMarkers {
+    // When each foot touches down in the walk and run clips
+    pub walk_footfalls: Vec<(Foot, f32)>,
+    pub run_footfalls: Vec<(Foot, f32)>,
+    // When the roll's i-frames end. They open as the roll starts, and the clip is sped up or slowed down
+    // so they last as long as the roll's own i-frames after equip load.
+    pub roll_iframes: f32,
+    // Hitbox windows of the attack clips. Without them, hitboxes follow the timing of the character's weapon.
+    #[serde(default)]
+    pub attacks: Option<AttackMarkers>,
+}
+
+/// When each attack clip's hitbox opens and closes
+#[derive(Reflect, Debug, Clone, Copy, Deserialize)]
+pub struct AttackMarkers {
+    pub light: (f32, f32),
+    pub heavy: (f32, f32),
+}
+
+/// Turns a `/`-separated bone path into the id the animation system knows the bone by
+pub fn bone_target(path: &str) -> AnimationTargetId {
+    AnimationTargetId::from_iter(path.split('/'))
+}
+
+impl MaskGroupPath {
+    /// The ids of every bone in the group
+    pub fn targets(&self) -> Vec<AnimationTargetId> {
+        let suffix: Vec<&str> = self.suffix.split('/').collect();
+        (0..=suffix.len())
+            .map(|chain_length| AnimationTargetId::from_iter(self.prefix.split('/').chain(suffix[..chain_length].iter().copied())))
+            .collect()
+    }
+}
+
+impl CharacterDefinition {
+    pub fn collider(&self) -> Collider {
+        Collider::capsule(self.capsule_radius, self.capsule_length)
+    }
+
+    /// Navmesh agent the size of this character's body, with the controller's default slope limit
+    pub fn nav_agent(&self) -> NavAgent {
+        let height = (self.capsule_length + 2.0 * self.capsule_radius) * self.scale;
+        NavAgent {
+            radius: self.capsule_radius * self.scale,
+            height,
+            max_slope: DEFAULT_MAX_SLOPE_DEGREES.to_radians(),
+            step_height: height / 3.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CharacterDefinitionLoader;
+
+impl AssetLoader for CharacterDefinitionLoader {
+    type Asset = CharacterDefinition;
+    type Settings = ();
+    type Error = RonFileError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["character.ron"]
+    }
+}
+
+/// The definition a character's model and body come from. Until it has loaded the character
+/// keeps the body it was spawned with and has no model.
+#[derive(Component)]
+pub struct CharacterModel(pub Handle<CharacterDefinition>);
+
+impl CharacterModel {
+    /// Body a character is spawned with before its definition has loaded. The world waits for the
+    /// definitions behind the loading screen, so nothing ever moves on it.
+    pub fn placeholder_collider() -> Collider {
+        Collider::sphere(0.5)
+    }
+}
+
+/// Gives characters the model, scale and body of their definition once it has loaded
+fn spawn_character_models(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    definitions: Res<Assets<CharacterDefinition>>,
+    mut characters: Query<(Entity, &CharacterModel, &mut Transform), Without<SceneRoot>>,
+) {
+    for (entity, model, mut transform) in &mut characters {
+        let Some(definition) = definitions.get(&model.0) else {
+            continue;
+        };
+
+        transform.scale = Vec3::splat(definition.scale);
+        let collider = definition.collider();
+        commands.entity(entity).insert((
+            SceneRoot(asset_server.load(GltfAssetLabel::Scene(0).from_asset(definition.model.clone()))),
+            CharacterController::shape_caster(&collider),
+            collider,
+        ));
+    }
+}
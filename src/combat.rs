@@ -0,0 +1,192 @@
+mod melee;
+
+use bevy::prelude::*;
+use crate::character_controller::Character;
+use crate::game_states::AppState;
+use crate::player::Player;
+pub use melee::*;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .register_type::<Focus>()
+            .register_type::<MeleeWeapon>()
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(FixedUpdate, (
+                melee::update_attacks,
+                melee::follow_attackers,
+                melee::apply_hitbox_hits,
+                apply_damage,
+                handle_player_death,
+            ).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+/// Hit points for anything that can be hurt by a [`DamageEvent`].
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Current health as a 0..1 fraction of the maximum.
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 {
+            (self.current / self.max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn refill(&mut self) {
+        self.current = self.max;
+    }
+}
+
+/// Focus points, the pool that spells and weapon skills draw from.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct Focus {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Focus {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Current focus as a 0..1 fraction of the maximum.
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 {
+            (self.current / self.max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn refill(&mut self) {
+        self.current = self.max;
+    }
+}
+
+/// The kind of damage carried by a [`DamageEvent`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Fall, // Environmental damage, can't be blocked
+}
+
+impl DamageType {
+    /// Whether a raised guard can absorb this kind of damage.
+    pub fn is_blockable(self) -> bool {
+        !matches!(self, DamageType::Fall)
+    }
+}
+
+/// Event to hurt an entity that has a [`Health`] component
+#[derive(Event, Debug, Clone)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// Who dealt the damage, if anyone. Used to check if a block is facing the attack.
+    pub source: Option<Entity>,
+    pub amount: f32,
+    pub damage_type: DamageType,
+}
+
+/// Sent once when an entity's health reaches zero
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+/// Applies queued damage to health, letting a raised guard absorb hits from the front
+fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut targets: Query<(&mut Health, Option<&mut Character>, &GlobalTransform)>,
+    transforms: Query<&GlobalTransform>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut health, character, target_transform)) = targets.get_mut(event.target) else {
+            continue;
+        };
+
+        // Already dead, ignore any hits that arrive in the same frame
+        if health.is_dead() {
+            continue;
+        }
+
+        // Dodged through the hit
+        if character.as_ref().is_some_and(|character| character.is_invulnerable()) {
+            continue;
+        }
+
+        let mut amount = event.amount;
+
+        if let Some(mut character) = character {
+            // Hits with no known source are treated as coming from the front
+            let from_front = event.source
+                .and_then(|source| transforms.get(source).ok())
+                .map_or(true, |source_transform| {
+                    // The character model faces +Z
+                    let facing = target_transform.rotation() * Vec3::Z;
+                    let to_source = source_transform.translation() - target_transform.translation();
+                    Vec3::new(facing.x, 0.0, facing.z)
+                        .dot(Vec3::new(to_source.x, 0.0, to_source.z)) >= 0.0
+                });
+
+            if character.is_blocking && from_front && event.damage_type.is_blockable() {
+                if character.stamina >= character.block_stamina_cost_per_hit {
+                    // Guard holds: absorb most of the hit at the cost of stamina
+                    character.stamina -= character.block_stamina_cost_per_hit;
+                    amount *= 1.0 - character.block_damage_reduction;
+                } else {
+                    // Guard break: not enough stamina to absorb the hit
+                    character.stamina = 0.0;
+                    character.exhausted = true;
+                    character.exhaustion_timer = 1.0;
+                    character.is_blocking = false;
+                }
+            }
+        }
+
+        health.current = (health.current - amount).max(0.0);
+
+        if health.is_dead() {
+            death_events.write(DeathEvent {
+                entity: event.target,
+                killer: event.source,
+            });
+        }
+    }
+}
+
+/// Switches to the death screen when the player dies
+fn handle_player_death(
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(), With<Player>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for event in death_events.read() {
+        if players.contains(event.entity) {
+            next_state.set(AppState::Death);
+        }
+    }
+}
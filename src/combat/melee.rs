@@ -0,0 +1,244 @@
+use avian3d::prelude::{Collider, CollidingEntities, Sensor};
+use bevy::prelude::*;
+use crate::animation::{AttackActive, AttackEnd};
+use crate::breakable::Breakable;
+use crate::character_controller::Character;
+use crate::combat::{DamageEvent, DamageType, Health};
+use crate::game_states::InWorld;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum AttackKind {
+    #[default]
+    Light,
+    Heavy,
+}
+
+/// Where an attack currently is in its timeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum AttackPhase {
+    #[default]
+    Idle,     // Not attacking
+    Startup,  // Wind-up, no hitbox yet
+    Active,   // Hitbox is live
+    Recovery, // Follow-through, hitbox is gone but the attacker is still committed
+}
+
+/// Timing, damage and reach of a single attack
+#[derive(Debug, Clone, Reflect)]
+pub struct AttackProfile {
+    pub startup: f32,
+    pub active: f32,
+    pub recovery: f32,
+    pub damage: f32,
+    pub damage_type: DamageType,
+    /// Size of the hitbox in world units, turned with the attacker but not scaled with it
+    pub hitbox_size: Vec3,
+    /// Hitbox center relative to the attacker in world units (+Z is forward)
+    pub hitbox_offset: Vec3,
+}
+
+impl AttackProfile {
+    pub fn duration(&self) -> f32 {
+        self.startup + self.active + self.recovery
+    }
+
+    /// Which phase an attack timed by its clip moves to, from the markers its clip reached this tick.
+    /// It still ends on the timer, so a clip that never fires its markers can't leave the attacker stuck.
+    pub fn marked_phase(&self, current: AttackPhase, elapsed: f32, active_marked: bool, end_marked: bool) -> AttackPhase {
+        if elapsed >= self.duration() {
+            AttackPhase::Idle
+        } else if current == AttackPhase::Startup && active_marked {
+            AttackPhase::Active
+        } else if current == AttackPhase::Active && end_marked {
+            AttackPhase::Recovery
+        } else {
+            current
+        }
+    }
+
+    /// Which phase the attack is in `elapsed` seconds after it started
+    pub fn phase_at(&self, elapsed: f32) -> AttackPhase {
+        if elapsed < self.startup {
+            AttackPhase::Startup
+        } else if elapsed < self.startup + self.active {
+            AttackPhase::Active
+        } else if elapsed < self.duration() {
+            AttackPhase::Recovery
+        } else {
+            AttackPhase::Idle
+        }
+    }
+}
+
+/// The light and heavy attacks available to a character
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct MeleeWeapon {
+    pub light: AttackProfile,
+    pub heavy: AttackProfile,
+}
+
+impl MeleeWeapon {
+    pub fn profile(&self, kind: AttackKind) -> &AttackProfile {
+        match kind {
+            AttackKind::Light => &self.light,
+            AttackKind::Heavy => &self.heavy,
+        }
+    }
+}
+
+impl Default for MeleeWeapon {
+    fn default() -> Self {
+        Self {
+            light: AttackProfile {
+                startup: 0.15,   // Quick wind-up
+                active: 0.15,
+                recovery: 0.3,
+                damage: 20.0,
+                damage_type: DamageType::Physical,
+                hitbox_size: Vec3::new(0.6, 0.6, 0.6),
+                hitbox_offset: Vec3::new(0.0, 0.15, 0.45),
+            },
+            heavy: AttackProfile {
+                startup: 0.45,   // Slow, readable wind-up
+                active: 0.2,
+                recovery: 0.55,
+                damage: 45.0,
+                damage_type: DamageType::Physical,
+                hitbox_size: Vec3::new(0.75, 0.6, 0.75),
+                hitbox_offset: Vec3::new(0.0, 0.15, 0.525),
+            },
+        }
+    }
+}
+
+/// Sensor collider that follows the attacker during an attack's active window. It isn't a child of
+/// the attacker, whose body would take it in as part of its own collider.
+#[derive(Component)]
+pub struct Hitbox {
+    pub owner: Entity,
+    pub offset: Vec3, // From the attacker, turned with it
+    pub damage: f32,
+    pub damage_type: DamageType,
+    /// Entities this swing already hit, so each target is damaged only once
+    pub already_hit: Vec<Entity>,
+}
+
+/// Advances attack phases and spawns/despawns the hitbox around the active window
+pub(crate) fn update_attacks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut attack_active: EventReader<AttackActive>,
+    mut attack_end: EventReader<AttackEnd>,
+    mut attackers: Query<(Entity, &mut Character, &MeleeWeapon, &Transform)>,
+    hitboxes: Query<(Entity, &Hitbox)>,
+) {
+    let delta = time.delta_secs();
+    let active_marked: Vec<Entity> = attack_active.read().map(|event| event.character).collect();
+    let end_marked: Vec<Entity> = attack_end.read().map(|event| event.character).collect();
+
+    for (entity, mut character, weapon, transform) in &mut attackers {
+        if !character.is_attacking {
+            // The attack may have been cancelled (e.g. by a respawn), don't leave a live hitbox behind
+            if character.attack_phase != AttackPhase::Idle {
+                character.attack_phase = AttackPhase::Idle;
+                despawn_hitboxes(&mut commands, entity, &hitboxes);
+            }
+            continue;
+        }
+
+        character.attack_timer += delta;
+
+        let profile = weapon.profile(character.current_attack);
+        let phase = if character.marked_attacks {
+            profile.marked_phase(
+                character.attack_phase,
+                character.attack_timer,
+                active_marked.contains(&entity),
+                end_marked.contains(&entity),
+            )
+        } else {
+            profile.phase_at(character.attack_timer)
+        };
+        if phase == character.attack_phase {
+            continue;
+        }
+
+        if phase == AttackPhase::Active {
+            commands.spawn((
+                Name::new("Hitbox"),
+                Hitbox {
+                    owner: entity,
+                    offset: profile.hitbox_offset,
+                    damage: profile.damage,
+                    damage_type: profile.damage_type,
+                    already_hit: Vec::new(),
+                },
+                Sensor,
+                Collider::cuboid(profile.hitbox_size.x, profile.hitbox_size.y, profile.hitbox_size.z),
+                hitbox_transform(transform, profile.hitbox_offset),
+                CollidingEntities::default(),
+                StateScoped(InWorld),
+            ));
+        } else {
+            despawn_hitboxes(&mut commands, entity, &hitboxes);
+        }
+
+        if phase == AttackPhase::Idle {
+            // Attack finished
+            character.is_attacking = false;
+            character.attack_timer = 0.0;
+        }
+        character.attack_phase = phase;
+    }
+}
+
+/// Where a hitbox sits for an attacker, `offset` turned with the attacker's facing
+fn hitbox_transform(attacker: &Transform, offset: Vec3) -> Transform {
+    Transform::from_translation(attacker.translation + attacker.rotation * offset).with_rotation(attacker.rotation)
+}
+
+/// Keeps hitboxes in front of their attackers, and removes those whose attacker is gone
+pub(crate) fn follow_attackers(
+    mut commands: Commands,
+    mut hitboxes: Query<(Entity, &Hitbox, &mut Transform)>,
+    attackers: Query<&Transform, Without<Hitbox>>,
+) {
+    for (entity, hitbox, mut transform) in &mut hitboxes {
+        match attackers.get(hitbox.owner) {
+            Ok(attacker) => *transform = hitbox_transform(attacker, hitbox.offset),
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+fn despawn_hitboxes(commands: &mut Commands, owner: Entity, hitboxes: &Query<(Entity, &Hitbox)>) {
+    for (hitbox_entity, hitbox) in hitboxes {
+        if hitbox.owner == owner {
+            commands.entity(hitbox_entity).despawn();
+        }
+    }
+}
+
+/// Sends a [`DamageEvent`] for everything a live hitbox touches
+pub(crate) fn apply_hitbox_hits(
+    mut hitboxes: Query<(&mut Hitbox, &CollidingEntities)>,
+    targets: Query<(), Or<(With<Health>, With<Breakable>)>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (mut hitbox, colliding_entities) in &mut hitboxes {
+        for &target in colliding_entities.iter() {
+            if target == hitbox.owner || hitbox.already_hit.contains(&target) || !targets.contains(target) {
+                continue;
+            }
+
+            hitbox.already_hit.push(target);
+            damage_events.write(DamageEvent {
+                target,
+                source: Some(hitbox.owner),
+                amount: hitbox.damage,
+                damage_type: hitbox.damage_type,
+            });
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Why a RON file couldn't be read or written
+#[derive(Debug)]
+pub enum RonFileError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for RonFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonFileError::Io(error) => write!(f, "couldn't access the file: {error}"),
+            RonFileError::Parse(error) => write!(f, "invalid file: {error}"),
+            RonFileError::Serialize(error) => write!(f, "couldn't serialize: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RonFileError {}
+
+impl From<std::io::Error> for RonFileError {
+    fn from(error: std::io::Error) -> Self {
+        RonFileError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for RonFileError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        RonFileError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for RonFileError {
+    fn from(error: ron::Error) -> Self {
+        RonFileError::Serialize(error)
+    }
+}
+
+/// Reads a file's text, or None if there isn't one
+pub fn read_text(path: impl AsRef<Path>) -> Result<Option<String>, RonFileError> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Reads a RON file, or None if there isn't one
+pub fn load_ron<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<Option<T>, RonFileError> {
+    let Some(text) = read_text(path)? else {
+        return Ok(None);
+    };
+    Ok(Some(ron::from_str(&text)?))
+}
+
+/// Reads a RON file, falling back to the default if there is none or it can't be read
+pub fn load_ron_or_default<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> T {
+    let path = path.as_ref();
+    match load_ron(path) {
+        Ok(value) => value.unwrap_or_default(),
+        Err(error) => {
+            warn!("Couldn't load {}, using defaults: {error}", path.display());
+            T::default()
+        }
+    }
+}
+
+/// Writes a RON file, creating its directory if needed. Goes through a temporary file so a crash
+/// mid-write doesn't destroy the previous one.
+pub fn save_ron<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<(), RonFileError> {
+    let path = path.as_ref();
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let temp_path = path.with_extension("ron.tmp");
+    fs::write(&temp_path, text)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use avian3d::prelude::{AngularVelocity, LinearVelocity};
+use bevy::prelude::*;
+use crate::bindings::{ActionInput, ActionMap, InputAction};
+use crate::bonfire::RespawnPoint;
+use crate::character_controller::{Character, InputBuffer};
+use crate::combat::Health;
+use crate::game_states::AppState;
+use crate::lock_on::LockOn;
+use crate::player::Player;
+
+pub struct DeathScreenPlugin;
+
+impl Plugin for DeathScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::Death), setup_death_screen)
+            .add_systems(Update, (
+                reveal_respawn_prompt,
+                respawn_player,
+            ).chain().run_if(in_state(AppState::Death)))
+            .add_systems(OnExit(AppState::Death), cleanup_death_screen);
+    }
+}
+
+// How long "YOU DIED" stays up before the player is allowed to respawn
+const RESPAWN_DELAY_SECS: f32 = 2.0;
+
+const DEATH_TEXT_COLOR: Color = Color::srgb(0.6, 0.05, 0.05);
+
+#[derive(Resource)]
+struct DeathScreenData {
+    root_entity: Entity,
+    respawn_timer: Timer,
+}
+
+/// Marker for the "press to respawn" hint, hidden until the respawn delay is over
+#[derive(Component)]
+struct RespawnPrompt;
+
+fn setup_death_screen(mut commands: Commands, action_map: Res<ActionMap>) {
+    let root_entity = commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(24.),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("YOU DIED"),
+                TextFont {
+                    font_size: 96.0,
+                    ..default()
+                },
+                TextColor(DEATH_TEXT_COLOR),
+            ));
+            parent.spawn((
+                Text::new(format!("Press {} to respawn", action_map.describe(InputAction::Interact))),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                Visibility::Hidden,
+                RespawnPrompt,
+            ));
+        })
+        .id();
+
+    commands.insert_resource(DeathScreenData {
+        root_entity,
+        respawn_timer: Timer::from_seconds(RESPAWN_DELAY_SECS, TimerMode::Once),
+    });
+}
+
+fn reveal_respawn_prompt(
+    time: Res<Time>,
+    mut death_screen: ResMut<DeathScreenData>,
+    mut prompts: Query<&mut Visibility, With<RespawnPrompt>>,
+) {
+    if death_screen.respawn_timer.tick(time.delta()).just_finished() {
+        for mut visibility in &mut prompts {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+/// Restores the player at the last bonfire rested at and goes back in game once a respawn is requested
+fn respawn_player(
+    input: ActionInput,
+    death_screen: Res<DeathScreenData>,
+    respawn_point: Res<RespawnPoint>,
+    mut players: Query<(
+        &mut Character,
+        &mut InputBuffer,
+        &mut Health,
+        &mut Transform,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+        &mut LockOn,
+    ), With<Player>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !death_screen.respawn_timer.finished() {
+        return;
+    }
+
+    if !input.just_pressed(InputAction::Interact) {
+        return;
+    }
+
+    for (mut player, mut buffer, mut health, mut transform, mut linear_velocity, mut angular_velocity, mut lock_on) in &mut players {
+        player.reset_state();
+        // Presses from just before dying would otherwise fire on the first frame back
+        buffer.clear();
+        lock_on.target = None;
+        health.refill();
+        transform.translation = respawn_point.position;
+        linear_velocity.0 = Vec3::ZERO;
+        angular_velocity.0 = Vec3::ZERO;
+    }
+
+    next_state.set(AppState::InGame);
+}
+
+fn cleanup_death_screen(mut commands: Commands, death_screen: Res<DeathScreenData>) {
+    commands.entity(death_screen.root_entity).despawn();
+    commands.remove_resource::<DeathScreenData>();
+}
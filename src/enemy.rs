@@ -0,0 +1,95 @@
+mod ai;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use crate::bonfire::RestEvent;
+use crate::character_controller::{ActionIntents, Character, CharacterController, ControllerSet};
+use crate::character_definition::CharacterModel;
+use crate::combat::{DeathEvent, Health, MeleeWeapon};
+use crate::game_states::{AppState, InWorld};
+use crate::lock_on::Targetable;
+use crate::perception::{update_perception, Perception};
+pub use ai::*;
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(InWorld), spawn_enemies)
+            .add_systems(Update, (despawn_enemies, spawn_enemies)
+                .chain()
+                .run_if(on_event::<RestEvent>))
+            .add_systems(FixedUpdate, ai::update_enemy_ai
+                .in_set(ControllerSet::Intents)
+                .after(update_perception))
+            .add_systems(FixedUpdate, lay_down_dead_enemies.run_if(in_state(AppState::InGame)));
+    }
+}
+
+/// Marks a hostile, AI-driven character
+#[derive(Component)]
+pub struct Enemy;
+
+/// Where each enemy starts and the route it walks while nothing is going on
+const ENEMY_SPAWNS: [(Vec3, &[Vec3]); 3] = [
+    (Vec3::new(12.0, 1.0, 12.0), &[Vec3::new(12.0, 1.0, 12.0), Vec3::new(8.0, 1.0, 16.0)]),
+    (Vec3::new(26.0, 1.0, 10.0), &[Vec3::new(26.0, 1.0, 10.0), Vec3::new(30.0, 1.0, 14.0), Vec3::new(26.0, 1.0, 18.0)]),
+    (Vec3::new(14.0, 1.0, 28.0), &[]), // Stands guard
+];
+
+// Model, body and rig of every enemy
+const ENEMY_CHARACTER_PATH: &str = "characters/enemy.character.ron";
+
+fn spawn_enemies(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let definition = asset_server.load(ENEMY_CHARACTER_PATH);
+
+    for (index, (position, patrol)) in ENEMY_SPAWNS.iter().enumerate() {
+        commands.spawn((
+            Name::new(format!("Enemy {index}")),
+            Enemy,
+            StateScoped(InWorld),
+            CharacterModel(definition.clone()),
+            Transform::from_translation(*position),
+            Character {
+                walk_speed: 120.0,    // Slower than the player so they can be outrun
+                run_speed: 260.0,
+                current_speed: 120.0,
+                ..default()
+            },
+            ActionIntents::default(),
+            EnemyAi::new(*position, patrol.to_vec()),
+            Perception::default(),
+            Health::new(60.0),
+            MeleeWeapon::default(),
+            Targetable::default(),
+            CharacterController::new(CharacterModel::placeholder_collider()),
+            Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
+            Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
+            GravityScale(2.0),
+            Mass(2.0),
+        ));
+    }
+}
+
+/// Clears out every enemy so resting can bring them all back
+fn despawn_enemies(mut commands: Commands, enemies: Query<Entity, With<Enemy>>) {
+    for entity in &enemies {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Dead enemies stop acting and are left where they fell, ragdolled by the animation, until resting
+/// clears them away
+fn lay_down_dead_enemies(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    mut enemies: Query<&mut Character, With<Enemy>>,
+) {
+    for event in death_events.read() {
+        if let Ok(mut character) = enemies.get_mut(event.entity) {
+            character.reset_state();
+            commands.entity(event.entity).remove::<(EnemyAi, Perception, Targetable)>();
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use avian3d::math::Vector2;
+use bevy::prelude::*;
+use crate::character_controller::{ActionIntents, Character, MovementAction};
+use crate::enemy::Enemy;
+use crate::navigation::NavMesh;
+use crate::perception::Perception;
+
+// How close an enemy has to get to a waypoint or home before it counts as arrived
+const ARRIVAL_DISTANCE: f32 = 0.5;
+
+// How far a destination can move before the path to it is recomputed
+const REPATH_DISTANCE: f32 = 1.0;
+
+// How far off the target an enemy may face and still start a swing
+const ATTACK_FACING_ANGLE: f32 = 0.5;
+
+/// What an enemy is currently doing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum AiState {
+    #[default]
+    Idle,       // Standing still, waiting to patrol
+    Patrol,     // Walking to the next patrol point
+    Chase,      // Running after a target, or to where it was last sensed
+    Attack,     // In range, facing the target and swinging
+    ReturnHome, // Lost interest, walking back to where it started
+}
+
+/// Decision making for an enemy, turned into [`ActionIntents`] every tick.
+/// What the enemy knows about the player comes from its [`Perception`].
+#[derive(Component, Debug)]
+pub struct EnemyAi {
+    pub state: AiState,
+    pub home: Vec3,
+    pub patrol_points: Vec<Vec3>,
+    pub patrol_index: usize,
+    pub state_timer: f32,       // Time spent in the current state
+
+    pub idle_duration: f32,     // Pause at each patrol point
+    pub leash_radius: f32,      // Gives up when dragged this far from home
+    pub attack_range: f32,      // Close enough to swing
+    pub attack_cooldown: f32,   // Time between swings
+    pub attack_cooldown_timer: f32,
+    pub heavy_attack_chance: f32,
+
+    path: Vec<Vec3>,            // Remaining waypoints to the current destination
+    path_goal: Option<Vec3>,    // Destination the path was found for
+}
+
+impl EnemyAi {
+    pub fn new(home: Vec3, patrol_points: Vec<Vec3>) -> Self {
+        Self {
+            state: AiState::Idle,
+            home,
+            patrol_points,
+            patrol_index: 0,
+            state_timer: 0.0,
+
+            idle_duration: 2.0,
+            leash_radius: 20.0,
+            attack_range: 1.5,
+            attack_cooldown: 1.2,
+            attack_cooldown_timer: 0.0,
+            heavy_attack_chance: 0.25,
+
+            path: Vec::new(),
+            path_goal: None,
+        }
+    }
+
+    fn set_state(&mut self, state: AiState) {
+        self.state = state;
+        self.state_timer = 0.0;
+        self.path.clear();
+        self.path_goal = None;
+    }
+
+    /// Queues a move along the navmesh toward `destination`, returns true once it has been reached.
+    /// Walks straight at it when there's no navmesh yet or no path was found.
+    fn navigate(
+        &mut self,
+        navmesh: Option<&NavMesh>,
+        intents: &mut ActionIntents,
+        from: Vec3,
+        destination: Vec3,
+        sprint: bool,
+    ) -> bool {
+        let Some(navmesh) = navmesh else {
+            return move_towards(intents, from, destination, sprint);
+        };
+
+        let stale = self.path_goal.is_none_or(|goal| flat_distance(goal, destination) > REPATH_DISTANCE);
+        if stale {
+            self.path = navmesh.find_path(from, destination).unwrap_or_else(|| vec![destination]);
+            self.path_goal = Some(destination);
+            // The first waypoint is where we're standing
+            if self.path.len() > 1 {
+                self.path.remove(0);
+            }
+        }
+
+        // Skip waypoints we've already reached, keeping the last so arrival can be reported
+        while self.path.len() > 1 && flat_distance(from, self.path[0]) <= ARRIVAL_DISTANCE {
+            self.path.remove(0);
+        }
+
+        let waypoint = self.path.first().copied().unwrap_or(destination);
+        move_towards(intents, from, waypoint, sprint) && self.path.len() <= 1
+    }
+}
+
+fn flat_distance(a: Vec3, b: Vec3) -> f32 {
+    Vec2::new(a.x - b.x, a.z - b.z).length()
+}
+
+/// Queues a move toward `destination`, returns true once it has been reached
+fn move_towards(intents: &mut ActionIntents, from: Vec3, destination: Vec3, sprint: bool) -> bool {
+    let offset = Vector2::new(destination.x - from.x, destination.z - from.z);
+    if offset.length() <= ARRIVAL_DISTANCE {
+        return true;
+    }
+
+    intents.push(MovementAction::Move(offset.normalize(), sprint));
+    false
+}
+
+/// Turns toward `point` on the ground plane (the model faces +Z), returns the flat direction to it
+fn turn_towards(transform: &mut Transform, point: Vec3, delta: f32) -> Vec3 {
+    let to_point = point - transform.translation;
+    let to_point = Vec3::new(to_point.x, 0.0, to_point.z).normalize_or_zero();
+    if to_point != Vec3::ZERO {
+        let target_rotation = Quat::from_rotation_y(f32::atan2(to_point.x, to_point.z));
+        transform.rotation = transform.rotation.slerp(target_rotation, 10.0 * delta);
+    }
+    to_point
+}
+
+/// Runs the enemy state machine and queues the resulting actions
+pub(crate) fn update_enemy_ai(
+    time: Res<Time>,
+    navmesh: Option<Res<NavMesh>>,
+    mut enemies: Query<(&mut EnemyAi, &mut Perception, &mut Transform, &Character, &mut ActionIntents), With<Enemy>>,
+) {
+    let delta = time.delta_secs();
+    let navmesh = navmesh.as_deref();
+
+    for (mut ai, mut perception, mut transform, character, mut intents) in &mut enemies {
+        ai.state_timer += delta;
+        ai.attack_cooldown_timer = (ai.attack_cooldown_timer - delta).max(0.0);
+
+        let position = transform.translation;
+        let distance_from_home = flat_distance(position, ai.home);
+
+        match ai.state {
+            AiState::Idle | AiState::Patrol => {
+                if perception.is_aggroed() {
+                    ai.set_state(AiState::Chase);
+                } else if let Some(sensed_position) = perception.last_known_position.filter(|_| perception.is_suspicious()) {
+                    // Heard or glimpsed something, stop and look that way
+                    turn_towards(&mut transform, sensed_position, delta);
+                } else if ai.state == AiState::Idle {
+                    if !ai.patrol_points.is_empty() && ai.state_timer >= ai.idle_duration {
+                        ai.set_state(AiState::Patrol);
+                    }
+                } else if ai.patrol_points.is_empty() {
+                    ai.set_state(AiState::Idle);
+                } else {
+                    let waypoint = ai.patrol_points[ai.patrol_index % ai.patrol_points.len()];
+                    if ai.navigate(navmesh, &mut intents, position, waypoint, false) {
+                        ai.patrol_index = (ai.patrol_index + 1) % ai.patrol_points.len();
+                        ai.set_state(AiState::Idle);
+                    }
+                }
+            }
+            AiState::Chase => {
+                // Aggro has decayed, or the enemy was dragged too far from home
+                if perception.awareness <= 0.0 || distance_from_home > ai.leash_radius {
+                    perception.forget();
+                    ai.set_state(AiState::ReturnHome);
+                    continue;
+                }
+
+                let Some(destination) = perception.last_known_position else { continue };
+                let distance = flat_distance(position, destination);
+
+                if perception.can_see_target && distance <= ai.attack_range {
+                    ai.set_state(AiState::Attack);
+                } else if ai.navigate(navmesh, &mut intents, position, destination, distance > ai.attack_range * 4.0) {
+                    // Reached where the target was last sensed, look around while aggro decays
+                    turn_towards(&mut transform, destination, delta);
+                }
+            }
+            AiState::Attack => {
+                // Committed to the swing until it finishes
+                if character.is_attacking {
+                    continue;
+                }
+
+                let target_position = perception.last_known_position.filter(|_| perception.can_see_target);
+                let Some(target_position) = target_position else {
+                    ai.set_state(AiState::Chase);
+                    continue;
+                };
+
+                if flat_distance(position, target_position) > ai.attack_range * 1.25 {
+                    ai.set_state(AiState::Chase);
+                    continue;
+                }
+
+                // Turn to face the target between swings
+                let to_target = turn_towards(&mut transform, target_position, delta);
+                let facing = transform.rotation * Vec3::Z;
+                let facing = Vec3::new(facing.x, 0.0, facing.z).normalize_or_zero();
+                if ai.attack_cooldown_timer <= 0.0 && facing.angle_between(to_target) <= ATTACK_FACING_ANGLE {
+                    let attack = if rand::random::<f32>() < ai.heavy_attack_chance {
+                        MovementAction::HeavyAttack
+                    } else {
+                        MovementAction::LightAttack
+                    };
+                    intents.push(attack);
+                    ai.attack_cooldown_timer = ai.attack_cooldown;
+                }
+            }
+            AiState::ReturnHome => {
+                // Pick the fight back up if provoked again while still near home
+                if perception.is_aggroed() && distance_from_home < ai.leash_radius * 0.5 {
+                    ai.set_state(AiState::Chase);
+                } else {
+                    let home = ai.home;
+                    if ai.navigate(navmesh, &mut intents, position, home, false) {
+                        ai.set_state(AiState::Idle);
+                    }
+                }
+            }
+        }
+    }
+}
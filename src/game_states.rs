@@ -1,13 +1,34 @@
 use bevy::app::{App, Plugin};
-use bevy::prelude::{AppExtStates, States};
+use bevy::prelude::{AppExtStates, ComputedStates, States};
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum AppState {
     #[default]
     Menu,
+    Loading, // World is spawned but waiting on its assets, see `LoadingPlugin`
     InGame,
     // Inventory,
-    // Death
+    Paused,
+    Death,
+}
+
+/// Active for as long as a game world exists, whether it's still loading, the
+/// player is alive, paused or looking at the death screen. World setup runs when this state is
+/// entered so that respawning (Death -> InGame) doesn't spawn everything a
+/// second time, and entities marked `StateScoped(InWorld)` are despawned when
+/// going back to the title.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct InWorld;
+
+impl ComputedStates for InWorld {
+    type SourceStates = AppState;
+
+    fn compute(sources: AppState) -> Option<Self> {
+        match sources {
+            AppState::Loading | AppState::InGame | AppState::Paused | AppState::Death => Some(InWorld),
+            AppState::Menu => None,
+        }
+    }
 }
 
 pub struct GameStatePlugin;
@@ -15,7 +36,8 @@ pub struct GameStatePlugin;
 impl Plugin for GameStatePlugin{
     fn build(&self, app: &mut App) {
         app
-            .init_state::<AppState>(); // Alternatively we could use .insert_state(AppState::Menu)
+            .init_state::<AppState>() // Alternatively we could use .insert_state(AppState::Menu)
+            .add_computed_state::<InWorld>()
+            .enable_state_scoped_entities::<InWorld>();
     }
 }
-
@@ -0,0 +1,308 @@
+use std::fmt;
+use bevy::core_pipeline::bloom::Bloom;
+use bevy::core_pipeline::experimental::taa::TemporalAntiAliasing;
+use bevy::core_pipeline::motion_blur::MotionBlur;
+use bevy::core_pipeline::prepass::{DepthPrepass, MotionVectorPrepass, NormalPrepass};
+use bevy::pbr::{Atmosphere, AtmosphereSettings, ScreenSpaceAmbientOcclusion, ScreenSpaceAmbientOcclusionQualityLevel, VolumetricFog};
+use bevy::prelude::*;
+use bevy::render::camera::{MipBias, TemporalJitter};
+use serde::{Deserialize, Serialize};
+use crate::camera::ThirdPersonCamera;
+use crate::config::{self, RonFileError};
+
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GraphicsSettings::load_or_default())
+            .add_systems(Update, apply_graphics_settings);
+    }
+}
+
+const GRAPHICS_PATH: &str = "config/graphics.ron";
+
+/// Quality presets, each a fixed set of effects. Changing a single effect makes it `Custom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GraphicsPreset {
+    Low,
+    Medium,
+    High,
+    Custom,
+}
+
+impl GraphicsPreset {
+    /// The preset after this one when cycling through them on the settings screen
+    pub fn next(self) -> Self {
+        match self {
+            GraphicsPreset::Low => GraphicsPreset::Medium,
+            GraphicsPreset::Medium => GraphicsPreset::High,
+            GraphicsPreset::High | GraphicsPreset::Custom => GraphicsPreset::Low,
+        }
+    }
+}
+
+impl fmt::Display for GraphicsPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A camera effect that can be turned on and off on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsOption {
+    Bloom,
+    Ssao,
+    Taa,
+    Msaa,
+    MotionBlur,
+    VolumetricFog,
+    DistanceFog,
+    Atmosphere,
+    EnvironmentMap,
+}
+
+impl GraphicsOption {
+    /// Every option, in the order they are listed on the settings screen
+    pub const ALL: [GraphicsOption; 9] = [
+        GraphicsOption::Bloom,
+        GraphicsOption::Ssao,
+        GraphicsOption::Taa,
+        GraphicsOption::Msaa,
+        GraphicsOption::MotionBlur,
+        GraphicsOption::VolumetricFog,
+        GraphicsOption::DistanceFog,
+        GraphicsOption::Atmosphere,
+        GraphicsOption::EnvironmentMap,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GraphicsOption::Bloom => "Bloom",
+            GraphicsOption::Ssao => "Ambient Occlusion",
+            GraphicsOption::Taa => "Temporal Anti-Aliasing",
+            GraphicsOption::Msaa => "MSAA",
+            GraphicsOption::MotionBlur => "Motion Blur",
+            GraphicsOption::VolumetricFog => "Volumetric Fog",
+            GraphicsOption::DistanceFog => "Distance Fog",
+            GraphicsOption::Atmosphere => "Atmosphere",
+            GraphicsOption::EnvironmentMap => "Environment Lighting",
+        }
+    }
+}
+
+/// Which post-processing effects the game camera uses, read from and saved to `config/graphics.ron`
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub preset: GraphicsPreset,
+    pub bloom: bool,
+    pub ssao: bool,
+    pub taa: bool,
+    pub msaa: bool, // Only used when neither SSAO nor TAA is on, both need MSAA off
+    pub motion_blur: bool,
+    pub volumetric_fog: bool,
+    pub distance_fog: bool,
+    pub atmosphere: bool,
+    pub environment_map: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self::from_preset(GraphicsPreset::High)
+    }
+}
+
+impl GraphicsSettings {
+    /// The effects a preset turns on. `Custom` starts from High.
+    pub fn from_preset(preset: GraphicsPreset) -> Self {
+        match preset {
+            GraphicsPreset::Low => Self {
+                preset,
+                bloom: false,
+                ssao: false,
+                taa: false,
+                msaa: true,
+                motion_blur: false,
+                volumetric_fog: false,
+                distance_fog: true,
+                atmosphere: false,
+                environment_map: true,
+            },
+            GraphicsPreset::Medium => Self {
+                preset,
+                bloom: true,
+                ssao: false,
+                taa: false,
+                msaa: true,
+                motion_blur: false,
+                volumetric_fog: false,
+                distance_fog: true,
+                atmosphere: true,
+                environment_map: true,
+            },
+            GraphicsPreset::High | GraphicsPreset::Custom => Self {
+                preset,
+                bloom: true,
+                ssao: true,
+                taa: true,
+                msaa: false,
+                motion_blur: true,
+                volumetric_fog: true,
+                distance_fog: true,
+                atmosphere: true,
+                environment_map: true,
+            },
+        }
+    }
+
+    pub fn is_enabled(&self, option: GraphicsOption) -> bool {
+        match option {
+            GraphicsOption::Bloom => self.bloom,
+            GraphicsOption::Ssao => self.ssao,
+            GraphicsOption::Taa => self.taa,
+            GraphicsOption::Msaa => self.msaa,
+            GraphicsOption::MotionBlur => self.motion_blur,
+            GraphicsOption::VolumetricFog => self.volumetric_fog,
+            GraphicsOption::DistanceFog => self.distance_fog,
+            GraphicsOption::Atmosphere => self.atmosphere,
+            GraphicsOption::EnvironmentMap => self.environment_map,
+        }
+    }
+
+    /// Flips one effect, which no longer matches any preset
+    pub fn toggle(&mut self, option: GraphicsOption) {
+        let enabled = match option {
+            GraphicsOption::Bloom => &mut self.bloom,
+            GraphicsOption::Ssao => &mut self.ssao,
+            GraphicsOption::Taa => &mut self.taa,
+            GraphicsOption::Msaa => &mut self.msaa,
+            GraphicsOption::MotionBlur => &mut self.motion_blur,
+            GraphicsOption::VolumetricFog => &mut self.volumetric_fog,
+            GraphicsOption::DistanceFog => &mut self.distance_fog,
+            GraphicsOption::Atmosphere => &mut self.atmosphere,
+            GraphicsOption::EnvironmentMap => &mut self.environment_map,
+        };
+        *enabled = !*enabled;
+        self.preset = GraphicsPreset::Custom;
+    }
+
+    /// MSAA mode for the camera. SSAO and TAA don't work with MSAA, so they win over it.
+    pub fn msaa_mode(&self) -> Msaa {
+        if self.msaa && !self.ssao && !self.taa {
+            Msaa::Sample4
+        } else {
+            Msaa::Off
+        }
+    }
+
+    /// Inserts the enabled effects on a camera and removes the disabled ones
+    pub fn apply_to_camera(&self, camera: &mut EntityCommands, asset_server: &AssetServer) {
+        camera.insert(self.msaa_mode());
+
+        if self.bloom {
+            camera.insert(Bloom::NATURAL);
+        } else {
+            camera.remove::<Bloom>();
+        }
+
+        if self.ssao {
+            camera.insert(ScreenSpaceAmbientOcclusion {
+                quality_level: ScreenSpaceAmbientOcclusionQualityLevel::High,
+                constant_object_thickness: 4.0,
+            });
+        } else {
+            camera.remove::<ScreenSpaceAmbientOcclusion>();
+        }
+
+        if self.taa {
+            camera.insert(TemporalAntiAliasing::default());
+        } else {
+            // The jitter and mip bias TAA brings along would keep shaking and sharpening the image without it
+            camera.remove::<(TemporalAntiAliasing, TemporalJitter, MipBias)>();
+        }
+
+        if self.motion_blur {
+            camera.insert(MotionBlur {
+                samples: 8,
+                shutter_angle: 1.5,
+                ..default()
+            });
+        } else {
+            camera.remove::<MotionBlur>();
+        }
+
+        if self.volumetric_fog {
+            camera.insert(VolumetricFog {
+                ambient_intensity: 0.1,
+                ..default()
+            });
+        } else {
+            camera.remove::<VolumetricFog>();
+        }
+
+        if self.distance_fog {
+            camera.insert(DistanceFog {
+                color: Color::srgb_u8(43, 44, 100),
+                falloff: FogFalloff::Exponential {
+                    density: 15e-3,
+                },
+                ..default()
+            });
+        } else {
+            camera.remove::<DistanceFog>();
+        }
+
+        if self.atmosphere {
+            camera.insert((Atmosphere::EARTH, AtmosphereSettings::default()));
+        } else {
+            camera.remove::<(Atmosphere, AtmosphereSettings)>();
+        }
+
+        // Prepasses the effects above brought along are dropped once nothing turned on needs them
+        if !self.taa && !self.motion_blur {
+            camera.remove::<MotionVectorPrepass>();
+        }
+        if !self.ssao {
+            camera.remove::<NormalPrepass>();
+        }
+        if !self.taa && !self.motion_blur && !self.ssao {
+            camera.remove::<DepthPrepass>();
+        }
+
+        if self.environment_map {
+            camera.insert(EnvironmentMapLight {
+                diffuse_map: asset_server.load("environment_maps/pisa_diffuse_rgb9e5_zstd.ktx2"),
+                specular_map: asset_server.load("environment_maps/pisa_specular_rgb9e5_zstd.ktx2"),
+                intensity: 2000.0,
+                ..default()
+            });
+        } else {
+            camera.remove::<EnvironmentMapLight>();
+        }
+    }
+
+    /// Reads the saved settings, falling back to the High preset if there are none or they can't be read
+    pub fn load_or_default() -> Self {
+        config::load_ron_or_default(GRAPHICS_PATH)
+    }
+
+    pub fn save(&self) -> Result<(), RonFileError> {
+        config::save_ron(GRAPHICS_PATH, self)
+    }
+}
+
+/// Brings the live game camera in line with the settings whenever they change
+fn apply_graphics_settings(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    asset_server: Res<AssetServer>,
+    cameras: Query<Entity, With<ThirdPersonCamera>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for camera in &cameras {
+        settings.apply_to_camera(&mut commands.entity(camera), &asset_server);
+    }
+}
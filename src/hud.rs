@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use crate::character_controller::Character;
+use crate::combat::{Focus, Health};
+use crate::game_states::AppState;
+use crate::player::Player;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::InGame), setup_hud)
+            .add_systems(Update, (
+                update_stat_bars,
+                update_exhausted_label,
+            ).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), cleanup_hud);
+    }
+}
+
+const BAR_HEIGHT: f32 = 14.0;
+const BAR_BORDER: f32 = 2.0;
+
+const BAR_BACKGROUND: Color = Color::srgba(0.05, 0.05, 0.05, 0.75);
+const BAR_BORDER_COLOR: Color = Color::srgb(0.35, 0.3, 0.25);
+const TRAIL_COLOR: Color = Color::srgb(0.95, 0.85, 0.55);
+const EXHAUSTED_COLOR: Color = Color::srgb(0.55, 0.25, 0.1);
+
+// How long the damage segment stays put after a loss before it starts catching up
+const TRAIL_HOLD_SECS: f32 = 0.6;
+// How fast the damage segment catches up, in bar fractions per second
+const TRAIL_DRAIN_RATE: f32 = 0.5;
+
+/// Which of the player's resources a bar shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HudStat {
+    Health,
+    Focus,
+    Stamina,
+}
+
+impl HudStat {
+    fn width(self) -> f32 {
+        match self {
+            HudStat::Health => 360.0,
+            HudStat::Focus => 240.0,
+            HudStat::Stamina => 300.0,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            HudStat::Health => Color::srgb(0.65, 0.08, 0.08),
+            HudStat::Focus => Color::srgb(0.15, 0.3, 0.75),
+            HudStat::Stamina => Color::srgb(0.2, 0.6, 0.2),
+        }
+    }
+}
+
+/// A resource bar with a "damage taken" segment trailing behind the real value
+#[derive(Component)]
+struct StatBar {
+    stat: HudStat,
+    fill: Entity,
+    trail: Entity,
+    trailing: f32, // Fraction the damage segment reaches, never below the real value
+    hold_timer: f32, // Time left before the damage segment starts draining
+    last: Option<f32>, // Fraction seen last frame, None until the player is first read
+}
+
+/// Marker for the text shown next to the bars while the player is out of breath
+#[derive(Component)]
+struct ExhaustedLabel;
+
+#[derive(Resource)]
+struct HudData {
+    root_entity: Entity,
+}
+
+fn setup_hud(mut commands: Commands) {
+    let root_entity = commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(32.),
+                top: Val::Px(32.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.),
+                ..default()
+            },
+            Name::new("HUD"),
+        ))
+        .with_children(|parent| {
+            spawn_stat_bar(parent, HudStat::Health);
+            spawn_stat_bar(parent, HudStat::Focus);
+            spawn_stat_bar(parent, HudStat::Stamina);
+            parent.spawn((
+                Text::new("Exhausted"),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(EXHAUSTED_COLOR),
+                Visibility::Hidden,
+                ExhaustedLabel,
+            ));
+        })
+        .id();
+
+    commands.insert_resource(HudData { root_entity });
+}
+
+fn spawn_stat_bar(parent: &mut ChildSpawnerCommands, stat: HudStat) {
+    // Both segments are laid over the frame, the real value drawn on top of the trail
+    let segment = |color: Color| (
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(0.),
+            top: Val::Px(0.),
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            ..default()
+        },
+        BackgroundColor(color),
+    );
+
+    let mut frame = parent.spawn((
+        Node {
+            width: Val::Px(stat.width()),
+            height: Val::Px(BAR_HEIGHT),
+            border: UiRect::all(Val::Px(BAR_BORDER)),
+            ..default()
+        },
+        BackgroundColor(BAR_BACKGROUND),
+        BorderColor(BAR_BORDER_COLOR),
+    ));
+
+    let mut trail = Entity::PLACEHOLDER;
+    let mut fill = Entity::PLACEHOLDER;
+    frame.with_children(|bar| {
+        trail = bar.spawn(segment(TRAIL_COLOR)).id();
+        fill = bar.spawn(segment(stat.color())).id();
+    });
+
+    frame.insert(StatBar {
+        stat,
+        fill,
+        trail,
+        trailing: 1.0,
+        hold_timer: 0.0,
+        last: None,
+    });
+}
+
+/// Resizes every bar to the player's values and lets the damage segments catch up
+fn update_stat_bars(
+    time: Res<Time>,
+    players: Query<(&Health, &Focus, &Character), With<Player>>,
+    mut bars: Query<(&mut StatBar, &mut BorderColor)>,
+    mut segments: Query<(&mut Node, &mut BackgroundColor)>,
+) {
+    let Ok((health, focus, character)) = players.single() else { return };
+    let delta = time.delta_secs();
+
+    for (mut bar, mut border) in &mut bars {
+        let current = match bar.stat {
+            HudStat::Health => health.fraction(),
+            HudStat::Focus => focus.fraction(),
+            HudStat::Stamina if character.max_stamina > 0.0 => {
+                (character.stamina / character.max_stamina).clamp(0.0, 1.0)
+            }
+            HudStat::Stamina => 0.0,
+        };
+
+        match bar.last {
+            // First look at the player, nothing to trail yet
+            None => bar.trailing = current,
+            Some(last) if current < last => bar.hold_timer = TRAIL_HOLD_SECS,
+            Some(_) => {}
+        }
+        bar.last = Some(current);
+
+        if current >= bar.trailing {
+            // Gains show up immediately
+            bar.trailing = current;
+        } else if bar.hold_timer > 0.0 {
+            bar.hold_timer -= delta;
+        } else {
+            bar.trailing = (bar.trailing - TRAIL_DRAIN_RATE * delta).max(current);
+        }
+
+        let exhausted = bar.stat == HudStat::Stamina && character.exhausted;
+
+        if let Ok((mut node, mut color)) = segments.get_mut(bar.fill) {
+            node.width = Val::Percent(current * 100.0);
+            color.0 = if exhausted {
+                // Pulse while out of breath
+                let pulse = (time.elapsed_secs() * 8.0).sin() * 0.5 + 0.5;
+                EXHAUSTED_COLOR.with_alpha(0.5 + 0.5 * pulse)
+            } else {
+                bar.stat.color()
+            };
+        }
+        if let Ok((mut node, _)) = segments.get_mut(bar.trail) {
+            node.width = Val::Percent(bar.trailing * 100.0);
+        }
+
+        border.0 = if exhausted { EXHAUSTED_COLOR } else { BAR_BORDER_COLOR };
+    }
+}
+
+fn update_exhausted_label(
+    players: Query<&Character, With<Player>>,
+    mut labels: Query<&mut Visibility, With<ExhaustedLabel>>,
+) {
+    let exhausted = players.single().is_ok_and(|character| character.exhausted);
+    for mut visibility in &mut labels {
+        *visibility = if exhausted { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+fn cleanup_hud(mut commands: Commands, hud: Res<HudData>) {
+    commands.entity(hud.root_entity).despawn();
+    commands.remove_resource::<HudData>();
+}
@@ -0,0 +1,15 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Items carried by a character, in the order they were picked up
+#[derive(Component, Debug, Clone, Default)]
+pub struct Inventory {
+    pub items: Vec<ItemStack>,
+}
+
+/// Some number of the same item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub id: String,
+    pub count: u32,
+}
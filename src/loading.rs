@@ -0,0 +1,195 @@
+use avian3d::prelude::*;
+use bevy::asset::RecursiveDependencyLoadState;
+use bevy::ecs::system::SystemParam;
+use bevy::log::warn_once;
+use bevy::prelude::*;
+use bevy::scene::{SceneInstance, SceneSpawner};
+use crate::animation::Animations;
+use crate::character_definition::CharacterModel;
+use crate::game_states::AppState;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::Loading), setup_loading_screen)
+            .add_systems(Update, track_loading.run_if(in_state(AppState::Loading)))
+            .add_systems(OnExit(AppState::Loading), cleanup_loading_screen);
+    }
+}
+
+const PROGRESS_BAR_WIDTH: f32 = 480.0;
+
+#[derive(Resource)]
+struct LoadingScreenData {
+    root_entity: Entity,
+}
+
+/// Marker for the bar filled as assets become ready
+#[derive(Component)]
+struct LoadingProgressBar;
+
+/// Marker for the percentage text under the bar
+#[derive(Component)]
+struct LoadingProgressText;
+
+/// Holds the world still behind a loading screen while the world's setup systems stream it in
+fn setup_loading_screen(mut commands: Commands, mut physics_time: ResMut<Time<Physics>>) {
+    // Nothing may fall through a level whose colliders aren't built yet
+    physics_time.pause();
+
+    let root_entity = commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(16.),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Loading"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(PROGRESS_BAR_WIDTH),
+                        height: Val::Px(12.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.9, 0.8, 0.6)),
+                    LoadingProgressBar,
+                ));
+            parent.spawn((
+                Text::new("0%"),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.6, 0.6, 0.6)),
+                LoadingProgressText,
+            ));
+        })
+        .id();
+
+    commands.insert_resource(LoadingScreenData { root_entity });
+}
+
+/// Whether an asset is done loading. A failed asset counts as done so a missing file can't hang the game.
+fn asset_done(asset_server: &AssetServer, id: impl Into<UntypedAssetId>) -> bool {
+    let id = id.into();
+    match asset_server.get_recursive_dependency_load_state(id) {
+        Some(RecursiveDependencyLoadState::Loaded) => true,
+        Some(RecursiveDependencyLoadState::Failed(error)) => {
+            warn_once!("An asset failed to load, continuing without it: {error}");
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Everything streamed in from disk that the world waits on
+#[derive(SystemParam)]
+struct WorldAssets<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    scene_spawner: Res<'w, SceneSpawner>,
+    scenes: Query<'w, 's, (&'static SceneRoot, Option<&'static SceneInstance>)>,
+    animations: Res<'w, Animations>,
+    // Characters whose definition hasn't given them a model yet
+    unmodelled: Query<'w, 's, &'static CharacterModel, Without<SceneRoot>>,
+}
+
+impl WorldAssets<'_, '_> {
+    /// How many of the assets are ready, out of how many
+    fn progress(&self) -> (usize, usize) {
+        let mut total = 0;
+        let mut ready = 0;
+
+        // Level, character and prop glTFs, loaded and spawned into the world
+        for (scene, instance) in &self.scenes {
+            total += 1;
+            let spawned = instance.is_some_and(|instance| self.scene_spawner.instance_is_ready(**instance));
+            if asset_done(&self.asset_server, &scene.0) && spawned {
+                ready += 1;
+            }
+        }
+
+        // Once loaded the definition gives its character a scene to wait on instead. One that failed
+        // to load never will, so it counts as done.
+        for model in &self.unmodelled {
+            total += 1;
+            if self.asset_server.load_state(&model.0).is_failed() {
+                warn_once!("A character definition failed to load, continuing without its model");
+                ready += 1;
+            }
+        }
+
+        for clip in self.animations.clips() {
+            total += 1;
+            if asset_done(&self.asset_server, clip) {
+                ready += 1;
+            }
+        }
+
+        (ready, total)
+    }
+}
+
+/// Enters the game once every scene is spawned, the animation clips are loaded and the level colliders are built
+fn track_loading(
+    assets: WorldAssets,
+    pending_colliders: Query<(), Or<(With<ColliderConstructorHierarchy>, With<ColliderConstructor>)>>,
+    mut bars: Query<&mut Node, With<LoadingProgressBar>>,
+    mut texts: Query<&mut Text, With<LoadingProgressText>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let (mut ready, mut total) = assets.progress();
+
+    // Colliders are built from the level meshes once the scenes are in, the last step
+    total += 1;
+    let colliders_built = pending_colliders.is_empty();
+    if colliders_built && ready == total - 1 {
+        ready += 1;
+    }
+
+    let progress = ready as f32 / total as f32;
+    for mut bar in &mut bars {
+        bar.width = Val::Percent(progress * 100.0);
+    }
+    for mut text in &mut texts {
+        text.0 = format!("{:.0}%", progress * 100.0);
+    }
+
+    if ready == total {
+        next_state.set(AppState::InGame);
+    }
+}
+
+fn cleanup_loading_screen(
+    mut commands: Commands,
+    loading_screen: Res<LoadingScreenData>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    physics_time.unpause();
+    commands.entity(loading_screen.root_entity).despawn();
+    commands.remove_resource::<LoadingScreenData>();
+}
@@ -0,0 +1,233 @@
+use avian3d::prelude::SpatialQuery;
+use bevy::prelude::*;
+use crate::bindings::{ActionInput, InputAction};
+use crate::camera::{third_person_camera, ThirdPersonCamera};
+use crate::game_states::AppState;
+use crate::physics::has_line_of_sight;
+use crate::player::Player;
+
+pub struct LockOnPlugin;
+
+impl Plugin for LockOnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+            toggle_lock_on,
+            switch_lock_on_target,
+            validate_lock_on,
+        ).chain().before(third_person_camera).run_if(in_state(AppState::InGame)));
+    }
+}
+
+// Height above the player's origin that target searches and sight checks start from
+const EYE_HEIGHT: f32 = 0.3;
+
+/// Marks an entity that can be locked on to
+#[derive(Component, Clone, Copy)]
+pub struct Targetable {
+    /// Height above the entity's origin that the camera and sight checks aim at
+    pub focus_height: f32,
+}
+
+impl Default for Targetable {
+    fn default() -> Self {
+        Self { focus_height: 0.5 }
+    }
+}
+
+/// Lock-on state and tuning for the entity doing the targeting
+#[derive(Component)]
+pub struct LockOn {
+    pub target: Option<Entity>,
+    pub max_distance: f32,     // How far away a new target can be acquired
+    pub break_distance: f32,   // The lock drops when the target gets further than this
+    pub view_cone_angle: f32,  // Half-angle around the camera forward searched for targets
+    pub lost_sight_grace: f32, // How long the target may stay hidden before the lock drops
+    pub lost_sight_timer: f32,
+    pub flick_threshold: f32,  // Right stick deflection needed to switch targets
+    pub stick_recentered: bool, // The stick must return to center between flicks
+}
+
+impl Default for LockOn {
+    fn default() -> Self {
+        Self {
+            target: None,
+            max_distance: 20.0,
+            break_distance: 25.0,
+            view_cone_angle: 45f32.to_radians(),
+            lost_sight_grace: 1.0,
+            lost_sight_timer: 0.0,
+            flick_threshold: 0.7,
+            stick_recentered: true,
+        }
+    }
+}
+
+/// World position the camera and character should look at for the current lock-on target
+pub fn locked_target_focus(
+    lock_on: Option<&LockOn>,
+    targets: &Query<(&GlobalTransform, &Targetable)>,
+) -> Option<Vec3> {
+    let target = lock_on?.target?;
+    let (transform, targetable) = targets.get(target).ok()?;
+    Some(focus_point(transform, targetable))
+}
+
+fn focus_point(transform: &GlobalTransform, targetable: &Targetable) -> Vec3 {
+    transform.translation() + Vec3::Y * targetable.focus_height
+}
+
+/// Locks on to the best visible target in front of the camera, or releases the current lock
+fn toggle_lock_on(
+    input: ActionInput,
+    mut players: Query<(Entity, &GlobalTransform, &mut LockOn), With<Player>>,
+    camera_query: Query<&GlobalTransform, With<ThirdPersonCamera>>,
+    targets: Query<(Entity, &GlobalTransform, &Targetable)>,
+    spatial_query: SpatialQuery,
+) {
+    if !input.just_pressed(InputAction::LockOn) {
+        return;
+    }
+
+    let Ok((player_entity, player_transform, mut lock_on)) = players.single_mut() else { return };
+
+    // Pressing again while locked releases the lock
+    if lock_on.target.take().is_some() {
+        return;
+    }
+
+    let Ok(camera_transform) = camera_query.single() else { return };
+    let camera_forward = camera_transform.forward();
+    let camera_forward = Vec3::new(camera_forward.x, 0.0, camera_forward.z).normalize_or_zero();
+    let eye = player_transform.translation() + Vec3::Y * EYE_HEIGHT;
+
+    // Prefer targets close to the center of the view, then close to the player
+    let best_target = targets
+        .iter()
+        .filter(|(entity, _, _)| *entity != player_entity)
+        .filter_map(|(entity, transform, targetable)| {
+            let focus = focus_point(transform, targetable);
+            let to_target = focus - eye;
+            let distance = to_target.length();
+            if distance > lock_on.max_distance {
+                return None;
+            }
+
+            let flat_direction = Vec3::new(to_target.x, 0.0, to_target.z).normalize_or_zero();
+            if flat_direction == Vec3::ZERO {
+                return None;
+            }
+
+            let angle = camera_forward.angle_between(flat_direction);
+            if angle > lock_on.view_cone_angle {
+                return None;
+            }
+
+            if !has_line_of_sight(&spatial_query, eye, focus, player_entity, entity) {
+                return None;
+            }
+
+            let score = 0.6 * angle / lock_on.view_cone_angle + 0.4 * distance / lock_on.max_distance;
+            Some((entity, score))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity);
+
+    lock_on.target = best_target;
+    lock_on.lost_sight_timer = 0.0;
+}
+
+/// Flicking the right stick moves the lock to the nearest target on that side
+fn switch_lock_on_target(
+    gamepads: Query<&Gamepad>,
+    mut players: Query<(Entity, &GlobalTransform, &mut LockOn), With<Player>>,
+    camera_query: Query<&GlobalTransform, With<ThirdPersonCamera>>,
+    targets: Query<(Entity, &GlobalTransform, &Targetable)>,
+    spatial_query: SpatialQuery,
+) {
+    let Ok((player_entity, player_transform, mut lock_on)) = players.single_mut() else { return };
+    let Some(current_target) = lock_on.target else { return };
+
+    // Use whichever connected gamepad has the strongest horizontal deflection
+    let stick_x = gamepads
+        .iter()
+        .filter_map(|gamepad| gamepad.get(GamepadAxis::RightStickX))
+        .fold(0.0_f32, |strongest, x| if x.abs() > strongest.abs() { x } else { strongest });
+
+    if stick_x.abs() < 0.3 {
+        lock_on.stick_recentered = true;
+        return;
+    }
+    if !lock_on.stick_recentered || stick_x.abs() < lock_on.flick_threshold {
+        return;
+    }
+    lock_on.stick_recentered = false;
+
+    let Ok(camera_transform) = camera_query.single() else { return };
+    let Ok((_, current_transform, current_targetable)) = targets.get(current_target) else { return };
+
+    let camera_right = *camera_transform.right();
+    let current_focus = focus_point(current_transform, current_targetable);
+    let eye = player_transform.translation() + Vec3::Y * EYE_HEIGHT;
+    let side = stick_x.signum();
+
+    let next_target = targets
+        .iter()
+        .filter(|(entity, _, _)| *entity != current_target && *entity != player_entity)
+        .filter_map(|(entity, transform, targetable)| {
+            let focus = focus_point(transform, targetable);
+            if focus.distance(eye) > lock_on.max_distance {
+                return None;
+            }
+
+            // How far the candidate sits to the requested side of the current target
+            let lateral_offset = (focus - current_focus).dot(camera_right) * side;
+            if lateral_offset <= 0.0 {
+                return None;
+            }
+
+            if !has_line_of_sight(&spatial_query, eye, focus, player_entity, entity) {
+                return None;
+            }
+
+            Some((entity, lateral_offset))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity);
+
+    if let Some(next_target) = next_target {
+        lock_on.target = Some(next_target);
+        lock_on.lost_sight_timer = 0.0;
+    }
+}
+
+/// Drops the lock when the target is gone, too far away or hidden for too long
+fn validate_lock_on(
+    time: Res<Time>,
+    mut players: Query<(Entity, &GlobalTransform, &mut LockOn), With<Player>>,
+    targets: Query<(&GlobalTransform, &Targetable)>,
+    spatial_query: SpatialQuery,
+) {
+    let Ok((player_entity, player_transform, mut lock_on)) = players.single_mut() else { return };
+    let Some(target) = lock_on.target else { return };
+
+    let Ok((target_transform, targetable)) = targets.get(target) else {
+        lock_on.target = None;
+        return;
+    };
+
+    let eye = player_transform.translation() + Vec3::Y * EYE_HEIGHT;
+    let focus = focus_point(target_transform, targetable);
+    if focus.distance(eye) > lock_on.break_distance {
+        lock_on.target = None;
+        return;
+    }
+
+    if has_line_of_sight(&spatial_query, eye, focus, player_entity, target) {
+        lock_on.lost_sight_timer = 0.0;
+    } else {
+        lock_on.lost_sight_timer += time.delta_secs();
+        if lock_on.lost_sight_timer > lock_on.lost_sight_grace {
+            lock_on.target = None;
+        }
+    }
+}
@@ -9,6 +9,22 @@ mod physics;
 mod world;
 mod breakable;
 mod proc;
+mod combat;
+mod death;
+mod lock_on;
+mod enemy;
+mod perception;
+mod navigation;
+mod bonfire;
+mod inventory;
+mod save;
+mod tuning;
+mod bindings;
+mod hud;
+mod graphics;
+mod loading;
+mod character_definition;
+mod config;
 
 use bevy::prelude::*;
 use bevy::window::{WindowResolution};
@@ -39,14 +55,29 @@ fn main() {
         .add_plugins(EguiPlugin { enable_multipass_for_primary_context: true })
         .add_plugins(WorldInspectorPlugin::new())
         //.add_plugins(SkeinPlugin::default())
+        .add_plugins(game_states::GameStatePlugin)
+        .add_plugins(bindings::BindingsPlugin)
         .add_plugins(menu::MenuPlugin)
-        .add_plugins(animation::AnimationTestPlugin)
+        .add_plugins(loading::LoadingPlugin)
+        .add_plugins(character_definition::CharacterDefinitionPlugin)
+        .add_plugins(animation::CharacterAnimationPlugin)
         // .add_plugins(fx::FXPlugin)
         .add_plugins(physics::PhysicsPlugin)
         .add_plugins(player::PlayerPlugin)
+        .add_plugins(tuning::TuningPlugin)
+        .add_plugins(graphics::GraphicsPlugin)
         .add_plugins(camera::CameraPlugin)
         .add_plugins(world::WorldPlugin)
         .add_plugins(character_controller::CharacterControllerPlugin)
+        .add_plugins(combat::CombatPlugin)
+        .add_plugins(death::DeathScreenPlugin)
+        .add_plugins(hud::HudPlugin)
+        .add_plugins(lock_on::LockOnPlugin)
+        .add_plugins(perception::PerceptionPlugin)
+        .add_plugins(navigation::NavigationPlugin)
+        .add_plugins(enemy::EnemyPlugin)
+        .add_plugins(bonfire::BonfirePlugin)
+        .add_plugins(save::SavePlugin)
         // .add_plugins(proc::ProceduralPlugin)
         .run();
 }
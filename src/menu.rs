@@ -1,107 +1,300 @@
+mod controls;
+mod focus;
+mod pause;
+mod settings;
+
 use bevy::prelude::*;
+use crate::bindings::{ActionMap, InputAction};
 use crate::game_states::AppState;
+use crate::graphics::{GraphicsOption, GraphicsSettings};
+use crate::save::{read_slots, ActiveSaveSlot, PendingLoad, SaveData, SaveSlot};
+use focus::MenuButtonPressed;
 
 pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
-        app
-            .add_systems(Startup, setup)
+        app.init_resource::<focus::MenuFocus>()
+            .add_event::<MenuButtonPressed>()
             // This system runs when we enter `AppState::Menu`, during the `StateTransition` schedule.
             // All systems from the exit schedule of the state we're leaving are run first,
             // and then all systems from the enter schedule of the state we're entering are run second.
             .add_systems(OnEnter(AppState::Menu), setup_menu)
             // By contrast, update systems are stored in the `Update` schedule. They simply
             // check the value of the `State<T>` resource to see if they should run each frame.
-            .add_systems(Update, menu.run_if(in_state(AppState::Menu)))
+            .add_systems(Update, (
+                focus::press_menu_buttons.run_if(not(resource_exists::<controls::AwaitingBinding>)),
+                controls::capture_binding.run_if(resource_exists::<controls::AwaitingBinding>),
+                menu,
+                focus::color_menu_buttons,
+            ).chain().run_if(in_state(AppState::Menu)))
             .add_systems(OnExit(AppState::Menu), cleanup_menu)
-            .init_state::<AppState>(); // Alternatively we could use .insert_state(AppState::Menu)
+            .add_systems(Update, pause::pause_game.run_if(in_state(AppState::InGame)))
+            .add_systems(OnEnter(AppState::Paused), pause::setup_pause_menu)
+            .add_systems(Update, (
+                (pause::resume_game, focus::press_menu_buttons)
+                    .run_if(not(resource_exists::<controls::AwaitingBinding>)),
+                controls::capture_binding.run_if(resource_exists::<controls::AwaitingBinding>),
+                pause::pause_menu,
+                focus::color_menu_buttons,
+            ).chain().run_if(in_state(AppState::Paused)))
+            .add_systems(OnExit(AppState::Paused), (pause::unfreeze_world, cleanup_menu));
     }
 }
 
 
+/// Everything the open menu spawned, despawned together when it closes
 #[derive(Resource)]
 struct MenuData {
-    button_entity: Entity,
+    root_entity: Entity, // Every screen is spawned under this node
+    camera_entity: Option<Entity>, // The title brings its own camera, the pause menu draws over the game's
 }
 
-#[derive(Resource)]
-struct MenuCamera {
-    camera_entity: Entity,
+/// What a menu button does when pressed
+#[derive(Component, Debug, Clone, Copy)]
+enum MenuButton {
+    Continue,
+    NewGame,
+    LoadGame,
+    LoadSlot(usize),
+    Controls,
+    Rebind(InputAction),
+    ResetBindings,
+    Settings,
+    CyclePreset,
+    ToggleGraphics(GraphicsOption),
+    Quit,
+    ConfirmQuit,
+    Back,
+    // Pause menu
+    Resume,
+    ReturnToTitle,
 }
 
 const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
+const DISABLED_BUTTON: Color = Color::srgb(0.08, 0.08, 0.08);
 
-pub fn setup(mut commands: Commands) {
-    let camera_entity = commands.spawn(Camera2d).id();
-    commands.insert_resource(MenuCamera {camera_entity});
+/// Most recently saved slot, which Continue picks up from
+fn latest_save(slots: &[SaveSlot]) -> Option<(usize, &SaveData)> {
+    slots
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, save)| match save {
+            SaveSlot::Saved(data) => Some((slot, data)),
+            _ => None,
+        })
+        .max_by_key(|(_, data)| data.saved_at)
+}
+
+/// Slot a new game autosaves to: the first empty one, or else the oldest
+fn slot_for_new_game(slots: &[SaveSlot]) -> usize {
+    if let Some(empty) = slots.iter().position(|save| matches!(save, SaveSlot::Empty)) {
+        return empty;
+    }
+    slots
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, save)| match save {
+            SaveSlot::Saved(data) => data.saved_at,
+            _ => 0, // Unreadable slots are overwritten first
+        })
+        .map_or(0, |(slot, _)| slot)
 }
 
 fn setup_menu(mut commands: Commands) {
-    let button_entity = commands
+    // The game camera is gone once back from a world, so the menu brings its own
+    let camera_entity = commands.spawn(Camera2d).id();
+
+    let slots = read_slots();
+
+    let root_entity = commands
         .spawn(Node {
-            // center button
+            // center buttons
             width: Val::Percent(100.),
             height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
+            row_gap: Val::Px(12.),
             ..default()
         })
-        .with_children(|parent| {
-            parent
-                .spawn((
-                    Button,
-                    Node {
-                        width: Val::Px(150.),
-                        height: Val::Px(65.),
-                        // horizontally center child text
-                        justify_content: JustifyContent::Center,
-                        // vertically center child text
-                        align_items: AlignItems::Center,
-                        ..default()
-                    },
-                    BackgroundColor(NORMAL_BUTTON),
-                ))
-                .with_children(|parent| {
-                    parent.spawn((
-                        Text::new("Play"),
-                        TextFont {
-                            font_size: 33.0,
-                            ..default()
-                        },
-                        TextColor(Color::srgb(0.9, 0.9, 0.9)),
-                    ));
-                });
-        })
+        .with_children(|parent| spawn_main_screen(parent, &slots))
         .id();
-    commands.insert_resource(MenuData { button_entity });
+
+    commands.insert_resource(MenuData { root_entity, camera_entity: Some(camera_entity) });
+    commands.insert_resource(SaveSlots(slots));
+}
+
+/// Save slots read when the menu opened
+#[derive(Resource)]
+struct SaveSlots(Vec<SaveSlot>);
+
+fn spawn_main_screen(parent: &mut ChildSpawnerCommands, slots: &[SaveSlot]) {
+    parent.spawn((
+        Text::new("PIG SOULS"),
+        TextFont {
+            font_size: 96.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.8, 0.6)),
+        Node {
+            margin: UiRect::bottom(Val::Px(36.)),
+            ..default()
+        },
+    ));
+
+    let continue_action = latest_save(slots).map(|_| MenuButton::Continue);
+    spawn_button(parent, "Continue", continue_action);
+    spawn_button(parent, "New Game", Some(MenuButton::NewGame));
+    let has_saves = slots.iter().any(|save| !matches!(save, SaveSlot::Empty));
+    spawn_button(parent, "Load Game", has_saves.then_some(MenuButton::LoadGame));
+    spawn_button(parent, "Settings", Some(MenuButton::Settings));
+    spawn_button(parent, "Quit", Some(MenuButton::Quit));
+}
+
+fn spawn_load_screen(parent: &mut ChildSpawnerCommands, slots: &[SaveSlot]) {
+    for (slot, save) in slots.iter().enumerate() {
+        let (label, action) = match save {
+            SaveSlot::Empty => (format!("Slot {}: empty", slot + 1), None),
+            SaveSlot::Saved(data) => {
+                let location = data.respawn.bonfire.as_deref().unwrap_or("Start");
+                (format!("Slot {}: {location}", slot + 1), Some(MenuButton::LoadSlot(slot)))
+            }
+            // Say why instead of failing when it's picked
+            SaveSlot::Unreadable(reason) => (format!("Slot {}: can't load, {reason}", slot + 1), None),
+        };
+        spawn_button(parent, label, action);
+    }
+    spawn_button(parent, "Back", Some(MenuButton::Back));
+}
+
+/// A button that does `action` when pressed, greyed out and inert without one
+fn spawn_button(parent: &mut ChildSpawnerCommands, label: impl Into<String>, action: Option<MenuButton>) {
+    let node = Node {
+        min_width: Val::Px(250.),
+        height: Val::Px(65.),
+        padding: UiRect::horizontal(Val::Px(16.)),
+        // horizontally center child text
+        justify_content: JustifyContent::Center,
+        // vertically center child text
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let text = (
+        Text::new(label),
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+    );
+
+    match action {
+        Some(action) => {
+            parent.spawn((Button, action, node, BackgroundColor(NORMAL_BUTTON)))
+                .with_child((text, TextColor(Color::srgb(0.9, 0.9, 0.9))));
+        }
+        None => {
+            parent.spawn((node, BackgroundColor(DISABLED_BUTTON)))
+                .with_child((text, TextColor(Color::srgb(0.4, 0.4, 0.4))));
+        }
+    }
 }
 
 fn menu(
+    mut commands: Commands,
     mut next_state: ResMut<NextState<AppState>>,
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
-    >,
+    mut pressed: EventReader<MenuButtonPressed>,
+    menu_data: Res<MenuData>,
+    slots: Res<SaveSlots>,
+    mut action_map: ResMut<ActionMap>,
+    mut graphics: ResMut<GraphicsSettings>,
+    mut exit: EventWriter<AppExit>,
 ) {
-    for (interaction, mut color) in &mut interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
-                *color = PRESSED_BUTTON.into();
-                next_state.set(AppState::InGame);
+    for MenuButtonPressed(button) in pressed.read() {
+        match *button {
+            MenuButton::Continue => {
+                if let Some((slot, data)) = latest_save(&slots.0) {
+                    start_game(&mut commands, &mut next_state, slot, Some(data.clone()));
+                }
+            }
+            MenuButton::NewGame => {
+                start_game(&mut commands, &mut next_state, slot_for_new_game(&slots.0), None);
+            }
+            MenuButton::LoadSlot(slot) => {
+                if let SaveSlot::Saved(data) = &slots.0[slot] {
+                    start_game(&mut commands, &mut next_state, slot, Some(data.clone()));
+                }
             }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
+            MenuButton::LoadGame => {
+                commands.entity(menu_data.root_entity)
+                    .despawn_related::<Children>()
+                    .with_children(|parent| spawn_load_screen(parent, &slots.0));
             }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
+            MenuButton::Settings
+            | MenuButton::CyclePreset
+            | MenuButton::ToggleGraphics(_)
+            | MenuButton::Controls
+            | MenuButton::Rebind(_)
+            | MenuButton::ResetBindings => {
+                settings::press_settings_button(&mut commands, &menu_data, &mut action_map, &mut graphics, *button);
             }
+            MenuButton::Quit => {
+                commands.entity(menu_data.root_entity)
+                    .despawn_related::<Children>()
+                    .with_children(spawn_quit_confirm);
+            }
+            MenuButton::ConfirmQuit => {
+                exit.write(AppExit::Success);
+            }
+            MenuButton::Back => {
+                commands.remove_resource::<controls::AwaitingBinding>();
+                commands.entity(menu_data.root_entity)
+                    .despawn_related::<Children>()
+                    .with_children(|parent| spawn_main_screen(parent, &slots.0));
+            }
+            // Only on the pause menu
+            MenuButton::Resume | MenuButton::ReturnToTitle => {}
         }
     }
 }
 
-fn cleanup_menu(mut commands: Commands, menu_data: Res<MenuData>, camera_data: Res<MenuCamera>) {
-    commands.entity(menu_data.button_entity).despawn();
-    commands.entity(camera_data.camera_entity).despawn();
+/// Asks before quitting, Back returns to the screen the menu started from
+fn spawn_quit_confirm(parent: &mut ChildSpawnerCommands) {
+    parent.spawn((
+        Text::new("Quit the game?"),
+        TextFont {
+            font_size: 40.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+    ));
+    spawn_button(parent, "Quit", Some(MenuButton::ConfirmQuit));
+    spawn_button(parent, "Cancel", Some(MenuButton::Back));
+}
+
+/// Enters the game autosaving to `slot`, restoring `save` once the world is spawned
+fn start_game(
+    commands: &mut Commands,
+    next_state: &mut NextState<AppState>,
+    slot: usize,
+    save: Option<SaveData>,
+) {
+    commands.insert_resource(ActiveSaveSlot(slot));
+    match save {
+        Some(data) => commands.insert_resource(PendingLoad(data)),
+        None => commands.remove_resource::<PendingLoad>(),
+    }
+    next_state.set(AppState::Loading);
+}
+
+/// Despawns the whole menu tree and its camera, for the title and pause menus alike
+fn cleanup_menu(mut commands: Commands, menu_data: Res<MenuData>) {
+    commands.entity(menu_data.root_entity).despawn();
+    if let Some(camera_entity) = menu_data.camera_entity {
+        commands.entity(camera_entity).despawn();
+    }
+    commands.remove_resource::<MenuData>();
+    commands.remove_resource::<SaveSlots>();
+    commands.remove_resource::<controls::AwaitingBinding>();
 }
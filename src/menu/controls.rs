@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use crate::bindings::{ActionMap, Binding, InputAction};
+use super::{spawn_button, MenuButton, MenuData, NORMAL_BUTTON};
+
+/// The action waiting for a new input after its binding was clicked
+#[derive(Resource)]
+pub(super) struct AwaitingBinding(pub InputAction);
+
+/// Replaces whatever screen the menu shows with the controls list
+pub(super) fn show_controls_screen(
+    commands: &mut Commands,
+    menu_data: &MenuData,
+    action_map: &ActionMap,
+    awaiting: Option<InputAction>,
+) {
+    commands.entity(menu_data.root_entity)
+        .despawn_related::<Children>()
+        .with_children(|parent| spawn_controls_screen(parent, action_map, awaiting));
+}
+
+fn spawn_controls_screen(parent: &mut ChildSpawnerCommands, action_map: &ActionMap, awaiting: Option<InputAction>) {
+    let font = TextFont {
+        font_size: 22.0,
+        ..default()
+    };
+
+    for action in InputAction::ALL {
+        let bindings = if awaiting == Some(action) {
+            "Press a key or button (Esc to cancel)".to_string()
+        } else {
+            action_map.describe(action)
+        };
+
+        parent
+            .spawn(Node {
+                width: Val::Px(720.),
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::Center,
+                ..default()
+            })
+            .with_children(|row| {
+                row.spawn((Text::new(action.label()), font.clone(), TextColor(Color::srgb(0.9, 0.9, 0.9))));
+                row.spawn((
+                    Button,
+                    MenuButton::Rebind(action),
+                    Node {
+                        min_width: Val::Px(420.),
+                        height: Val::Px(36.),
+                        padding: UiRect::horizontal(Val::Px(12.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(NORMAL_BUTTON),
+                ))
+                    .with_child((Text::new(bindings), font.clone(), TextColor(Color::srgb(0.9, 0.9, 0.9))));
+            });
+    }
+
+    spawn_button(parent, "Reset to Defaults", Some(MenuButton::ResetBindings));
+    spawn_button(parent, "Back", Some(MenuButton::Settings));
+}
+
+/// Binds the first key, mouse button or gamepad button pressed to the action being rebound.
+/// Runs before the menu buttons so the click that started rebinding isn't captured.
+pub(super) fn capture_binding(
+    mut commands: Commands,
+    awaiting: Res<AwaitingBinding>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut action_map: ResMut<ActionMap>,
+    menu_data: Res<MenuData>,
+) {
+    if keyboard.just_pressed(KeyCode::Escape) {
+        commands.remove_resource::<AwaitingBinding>();
+        show_controls_screen(&mut commands, &menu_data, &action_map, None);
+        return;
+    }
+
+    let binding = keyboard.get_just_pressed().next().map(|key| Binding::Key(*key))
+        .or_else(|| mouse.get_just_pressed().next().map(|button| Binding::Mouse(*button)))
+        .or_else(|| gamepads.iter().find_map(|gamepad| gamepad.get_just_pressed().next().map(|button| Binding::Gamepad(*button))));
+    let Some(binding) = binding else { return };
+
+    action_map.rebind(awaiting.0, binding);
+    if let Err(error) = action_map.save() {
+        error!("Couldn't save bindings: {error}");
+    }
+
+    commands.remove_resource::<AwaitingBinding>();
+    show_controls_screen(&mut commands, &menu_data, &action_map, None);
+}
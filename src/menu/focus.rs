@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use super::{MenuButton, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
+
+// How far the left stick has to be pushed to move the focus
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// The button that keyboard and gamepad input acts on
+#[derive(Resource, Default)]
+pub(super) struct MenuFocus(Option<Entity>);
+
+/// Sent when a menu button is clicked, or confirmed with the keyboard or a gamepad while focused
+#[derive(Event, Debug, Clone, Copy)]
+pub(super) struct MenuButtonPressed(pub MenuButton);
+
+/// Turns clicks, and arrow keys, d-pad or stick plus Enter, Space or (A), into [`MenuButtonPressed`] events
+pub(super) fn press_menu_buttons(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut focus: ResMut<MenuFocus>,
+    mut stick_held: Local<bool>,
+    buttons: Query<(Entity, &MenuButton, Ref<Interaction>, &GlobalTransform, &InheritedVisibility)>,
+    mut pressed: EventWriter<MenuButtonPressed>,
+) {
+    for (entity, button, interaction, _, _) in &buttons {
+        if !interaction.is_changed() {
+            continue;
+        }
+        match *interaction {
+            Interaction::Pressed => {
+                pressed.write(MenuButtonPressed(*button));
+            }
+            // The mouse moves the focus too, so both never highlight different buttons for long
+            Interaction::Hovered => focus.0 = Some(entity),
+            Interaction::None => {}
+        }
+    }
+
+    // Buttons top to bottom, then left to right
+    let mut order: Vec<(Entity, Vec3)> = buttons
+        .iter()
+        .filter(|(.., visibility)| visibility.get())
+        .map(|(entity, _, _, transform, _)| (entity, transform.translation()))
+        .collect();
+    if order.is_empty() {
+        focus.0 = None;
+        return;
+    }
+    order.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    // Only move on the frame the stick is pushed, not every frame it's held
+    let stick = gamepads
+        .iter()
+        .map(|gamepad| gamepad.left_stick().y)
+        .find(|y| y.abs() > STICK_THRESHOLD);
+    let stick_pushed = !*stick_held && stick.is_some();
+    *stick_held = stick.is_some();
+
+    let up = keyboard.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW])
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::DPadUp))
+        || (stick_pushed && stick.is_some_and(|y| y > 0.0));
+    let down = keyboard.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS])
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::DPadDown))
+        || (stick_pushed && stick.is_some_and(|y| y < 0.0));
+
+    // A focused button that went away with its screen hands the focus to the first one of the new screen
+    let count = order.len();
+    let index = match focus.0.and_then(|focused| order.iter().position(|(entity, _)| *entity == focused)) {
+        Some(index) if down => (index + 1) % count,
+        Some(index) if up => (index + count - 1) % count,
+        Some(index) => index,
+        None => 0,
+    };
+    let focused = order[index].0;
+    focus.0 = Some(focused);
+
+    let confirm = keyboard.any_just_pressed([KeyCode::Enter, KeyCode::Space])
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::South));
+    if !confirm {
+        return;
+    }
+    if let Ok((_, button, ..)) = buttons.get(focused) {
+        pressed.write(MenuButtonPressed(*button));
+    }
+}
+
+/// Highlights the hovered or focused button
+pub(super) fn color_menu_buttons(
+    focus: Res<MenuFocus>,
+    mut buttons: Query<(Entity, &Interaction, &mut BackgroundColor), With<MenuButton>>,
+) {
+    for (entity, interaction, mut color) in &mut buttons {
+        let new_color = match interaction {
+            Interaction::Pressed => PRESSED_BUTTON,
+            Interaction::Hovered => HOVERED_BUTTON,
+            Interaction::None if focus.0 == Some(entity) => HOVERED_BUTTON,
+            Interaction::None => NORMAL_BUTTON,
+        };
+        color.set_if_neq(BackgroundColor(new_color));
+    }
+}
@@ -0,0 +1,136 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use crate::bindings::ActionMap;
+use crate::game_states::AppState;
+use crate::graphics::GraphicsSettings;
+use crate::save::SaveGameEvent;
+use super::focus::MenuButtonPressed;
+use super::{controls, settings, spawn_button, spawn_quit_confirm, MenuButton, MenuData};
+
+/// Whether Escape or a gamepad's Start button was just pressed
+fn pause_pressed(keyboard: &ButtonInput<KeyCode>, gamepads: &Query<&Gamepad>) -> bool {
+    keyboard.just_pressed(KeyCode::Escape)
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::Start))
+}
+
+pub(super) fn pause_game(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if pause_pressed(&keyboard, &gamepads) {
+        next_state.set(AppState::Paused);
+    }
+}
+
+/// The pause button closes the menu again. Doesn't run while a binding is being captured, Escape cancels that instead.
+pub(super) fn resume_game(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if pause_pressed(&keyboard, &gamepads) {
+        next_state.set(AppState::InGame);
+    }
+}
+
+/// Freezes the world and shows the pause menu over it
+pub(super) fn setup_pause_menu(
+    mut commands: Commands,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    // Gameplay timers read virtual time, and fixed updates are driven by it
+    virtual_time.pause();
+    physics_time.pause();
+
+    let root_entity = commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        ))
+        .with_children(spawn_pause_screen)
+        .id();
+
+    commands.insert_resource(MenuData { root_entity, camera_entity: None });
+}
+
+fn spawn_pause_screen(parent: &mut ChildSpawnerCommands) {
+    parent.spawn((
+        Text::new("PAUSED"),
+        TextFont {
+            font_size: 64.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+    ));
+    spawn_button(parent, "Resume", Some(MenuButton::Resume));
+    spawn_button(parent, "Settings", Some(MenuButton::Settings));
+    spawn_button(parent, "Return to Title", Some(MenuButton::ReturnToTitle));
+    spawn_button(parent, "Quit", Some(MenuButton::Quit));
+}
+
+pub(super) fn pause_menu(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut pressed: EventReader<MenuButtonPressed>,
+    menu_data: Res<MenuData>,
+    mut action_map: ResMut<ActionMap>,
+    mut graphics: ResMut<GraphicsSettings>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for MenuButtonPressed(button) in pressed.read() {
+        match *button {
+            MenuButton::Resume => {
+                next_state.set(AppState::InGame);
+            }
+            MenuButton::Settings
+            | MenuButton::CyclePreset
+            | MenuButton::ToggleGraphics(_)
+            | MenuButton::Controls
+            | MenuButton::Rebind(_)
+            | MenuButton::ResetBindings => {
+                settings::press_settings_button(&mut commands, &menu_data, &mut action_map, &mut graphics, *button);
+            }
+            // Saved while the world is still there, it's torn down when the state changes
+            MenuButton::ReturnToTitle => {
+                commands.send_event(SaveGameEvent);
+                next_state.set(AppState::Menu);
+            }
+            MenuButton::Quit => {
+                commands.entity(menu_data.root_entity)
+                    .despawn_related::<Children>()
+                    .with_children(spawn_quit_confirm);
+            }
+            MenuButton::ConfirmQuit => {
+                commands.send_event(SaveGameEvent);
+                exit.write(AppExit::Success);
+            }
+            MenuButton::Back => {
+                commands.remove_resource::<controls::AwaitingBinding>();
+                commands.entity(menu_data.root_entity)
+                    .despawn_related::<Children>()
+                    .with_children(spawn_pause_screen);
+            }
+            // Only on the title screen
+            _ => {}
+        }
+    }
+}
+
+/// Lets time and physics run again, the menu itself is cleaned up like the title's
+pub(super) fn unfreeze_world(
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    virtual_time.unpause();
+    physics_time.unpause();
+}
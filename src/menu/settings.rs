@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use crate::bindings::ActionMap;
+use crate::graphics::{GraphicsOption, GraphicsSettings};
+use super::{controls, spawn_button, MenuButton, MenuData, NORMAL_BUTTON};
+
+/// Replaces whatever screen the menu shows with the graphics settings
+pub(super) fn show_settings_screen(commands: &mut Commands, menu_data: &MenuData, graphics: &GraphicsSettings) {
+    commands.entity(menu_data.root_entity)
+        .despawn_related::<Children>()
+        .with_children(|parent| spawn_settings_screen(parent, graphics));
+}
+
+fn spawn_settings_screen(parent: &mut ChildSpawnerCommands, graphics: &GraphicsSettings) {
+    spawn_setting_row(parent, "Quality", graphics.preset.to_string(), MenuButton::CyclePreset);
+    for option in GraphicsOption::ALL {
+        let value = if graphics.is_enabled(option) { "On" } else { "Off" };
+        spawn_setting_row(parent, option.label(), value.to_string(), MenuButton::ToggleGraphics(option));
+    }
+
+    spawn_button(parent, "Controls", Some(MenuButton::Controls));
+    spawn_button(parent, "Back", Some(MenuButton::Back));
+}
+
+/// A setting's name with a button showing its value, pressed to change it
+fn spawn_setting_row(parent: &mut ChildSpawnerCommands, label: &str, value: String, action: MenuButton) {
+    let font = TextFont {
+        font_size: 22.0,
+        ..default()
+    };
+
+    parent
+        .spawn(Node {
+            width: Val::Px(520.),
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|row| {
+            row.spawn((Text::new(label), font.clone(), TextColor(Color::srgb(0.9, 0.9, 0.9))));
+            row.spawn((
+                Button,
+                action,
+                Node {
+                    min_width: Val::Px(160.),
+                    height: Val::Px(36.),
+                    padding: UiRect::horizontal(Val::Px(12.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(NORMAL_BUTTON),
+            ))
+                .with_child((Text::new(value), font.clone(), TextColor(Color::srgb(0.9, 0.9, 0.9))));
+        });
+}
+
+/// Handles the buttons of the settings and controls screens, for the title and pause menus alike
+pub(super) fn press_settings_button(
+    commands: &mut Commands,
+    menu_data: &MenuData,
+    action_map: &mut ActionMap,
+    graphics: &mut GraphicsSettings,
+    button: MenuButton,
+) {
+    match button {
+        MenuButton::Settings => {
+            commands.remove_resource::<controls::AwaitingBinding>();
+            show_settings_screen(commands, menu_data, graphics);
+        }
+        MenuButton::CyclePreset => {
+            *graphics = GraphicsSettings::from_preset(graphics.preset.next());
+            save_graphics(graphics);
+            show_settings_screen(commands, menu_data, graphics);
+        }
+        MenuButton::ToggleGraphics(option) => {
+            graphics.toggle(option);
+            save_graphics(graphics);
+            show_settings_screen(commands, menu_data, graphics);
+        }
+        MenuButton::Controls => {
+            controls::show_controls_screen(commands, menu_data, action_map, None);
+        }
+        MenuButton::Rebind(action) => {
+            commands.insert_resource(controls::AwaitingBinding(action));
+            controls::show_controls_screen(commands, menu_data, action_map, Some(action));
+        }
+        MenuButton::ResetBindings => {
+            *action_map = ActionMap::default();
+            if let Err(error) = action_map.save() {
+                error!("Couldn't save bindings: {error}");
+            }
+            controls::show_controls_screen(commands, menu_data, action_map, None);
+        }
+        _ => {}
+    }
+}
+
+fn save_graphics(graphics: &GraphicsSettings) {
+    if let Err(error) = graphics.save() {
+        error!("Couldn't save graphics settings: {error}");
+    }
+}
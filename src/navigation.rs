@@ -0,0 +1,268 @@
+mod navmesh;
+
+use avian3d::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use crate::breakable::Breakable;
+use crate::character_definition::{CharacterDefinition, CharacterModel};
+use crate::game_states::{AppState, InWorld};
+pub use navmesh::*;
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavMeshRebakeQueue>()
+            .add_observer(queue_rebake_on_break)
+            .add_systems(Update, (
+                start_navmesh_bake.run_if(not(resource_exists::<NavMesh>).and(not(resource_exists::<NavMeshBake>))),
+                continue_navmesh_bake.run_if(resource_exists::<NavMeshBake>),
+                rebake_dirty_regions.run_if(resource_exists::<NavMesh>),
+            ).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(InWorld), clear_navmesh);
+    }
+}
+
+// Navmesh cell size, about one agent diameter
+const CELL_SIZE: f32 = 0.3;
+// Cells grow instead of going past this count on very large levels
+const MAX_CELLS: f32 = 250_000.0;
+// Cells baked each frame, so a large level is baked over a few seconds instead of stalling one frame
+const BAKE_CELLS_PER_FRAME: usize = 10_000;
+// Frames to wait once the level colliders exist, so spatial queries can see them
+const BAKE_SETTLE_FRAMES: u32 = 5;
+// Time to wait after a prop breaks before its area is rebaked, so its collider is gone
+const REBAKE_DELAY_SECS: f32 = 0.2;
+
+/// The bodies of the characters in the world, from their definitions
+#[derive(SystemParam)]
+struct CharacterBodies<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    definitions: Res<'w, Assets<CharacterDefinition>>,
+    models: Query<'w, 's, &'static CharacterModel>,
+}
+
+impl CharacterBodies<'_, '_> {
+    /// Agent that fits the largest character in the world, or None while a definition is still loading.
+    /// Definitions that failed to load are left out.
+    fn nav_agent(&self) -> Option<NavAgent> {
+        let mut agent: Option<NavAgent> = None;
+        for model in &self.models {
+            let Some(definition) = self.definitions.get(&model.0) else {
+                if self.asset_server.load_state(&model.0).is_failed() {
+                    continue;
+                }
+                return None;
+            };
+            let body = definition.nav_agent();
+            agent = Some(match agent {
+                Some(largest) => NavAgent {
+                    radius: largest.radius.max(body.radius),
+                    height: largest.height.max(body.height),
+                    max_slope: largest.max_slope.min(body.max_slope),
+                    step_height: largest.step_height.min(body.step_height),
+                },
+                None => body,
+            });
+        }
+        agent
+    }
+}
+
+/// A navmesh being baked a slice of its cells each frame. Enemies walk straight at their targets until it's done.
+#[derive(Resource)]
+struct NavMeshBake {
+    navmesh: NavMesh,
+    cells: Vec<UVec2>,
+    baked: usize, // Cells of `cells` baked so far
+    walkable: usize,
+}
+
+/// Areas of the navmesh waiting to be rebaked after a prop was destroyed
+#[derive(Resource, Default)]
+struct NavMeshRebakeQueue(Vec<PendingRebake>);
+
+struct PendingRebake {
+    min: Vec2,
+    max: Vec2,
+    timer: Timer,
+}
+
+/// Whether a collider is level geometry the navmesh is built from: static bodies and intact props
+fn is_level_geometry(
+    entity: Entity,
+    colliders: &Query<(&ColliderOf, Has<Breakable>)>,
+    bodies: &Query<&RigidBody>,
+) -> bool {
+    colliders.get(entity).is_ok_and(|(collider_of, breakable)| {
+        breakable || bodies.get(collider_of.body).is_ok_and(|body| body.is_static())
+    })
+}
+
+/// Finds the ground under a cell and checks that the agent fits there
+fn bake_cell(
+    navmesh: &NavMesh,
+    cell: UVec2,
+    agent_shape: &Collider,
+    spatial_query: &SpatialQuery,
+    is_geometry: &dyn Fn(Entity) -> bool,
+) -> Option<f32> {
+    let center = navmesh.cell_center(cell);
+    let origin = Vec3::new(center.x, navmesh.ceiling, center.y);
+
+    let hit = spatial_query.cast_ray_predicate(
+        origin,
+        Dir3::NEG_Y,
+        navmesh.ceiling - navmesh.floor,
+        true,
+        &SpatialQueryFilter::default(),
+        is_geometry,
+    )?;
+
+    // Too steep to walk on, either side of a trimesh can be hit
+    let slope = hit.normal.dot(Vec3::Y).abs().acos();
+    if slope > navmesh.agent.max_slope {
+        return None;
+    }
+
+    // The agent standing here, lifted by a step, must not touch anything
+    let ground = origin.y - hit.distance;
+    let agent = &navmesh.agent;
+    let agent_center = Vec3::new(center.x, ground + agent.step_height + agent.height * 0.5, center.y);
+    let blocked = spatial_query
+        .shape_intersections(agent_shape, agent_center, Quat::IDENTITY, &SpatialQueryFilter::default())
+        .into_iter()
+        .any(is_geometry);
+
+    (!blocked).then_some(ground)
+}
+
+fn agent_shape(agent: &NavAgent) -> Collider {
+    Collider::capsule(agent.radius, (agent.height - 2.0 * agent.radius).max(0.0))
+}
+
+/// Starts baking the navmesh once the level's collider hierarchies have been built
+fn start_navmesh_bake(
+    mut commands: Commands,
+    mut settle_frames: Local<u32>,
+    pending_constructors: Query<(), With<ColliderConstructorHierarchy>>,
+    level_colliders: Query<(&ColliderAabb, &ColliderOf)>,
+    bodies: Query<&RigidBody>,
+    characters: CharacterBodies,
+) {
+    // Level scenes are still loading or waiting for their colliders
+    if !pending_constructors.is_empty() {
+        *settle_frames = 0;
+        return;
+    }
+
+    // Bounds of all static geometry
+    let bounds = level_colliders
+        .iter()
+        .filter(|(_, collider_of)| bodies.get(collider_of.body).is_ok_and(|body| body.is_static()))
+        .map(|(aabb, _)| (aabb.min, aabb.max))
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+    let Some((min, max)) = bounds else {
+        *settle_frames = 0;
+        return;
+    };
+
+    *settle_frames += 1;
+    if *settle_frames < BAKE_SETTLE_FRAMES {
+        return;
+    }
+
+    // Sized by the characters' definitions, so wait until they're in
+    let Some(agent) = characters.nav_agent() else {
+        return;
+    };
+    let area = (max.x - min.x) * (max.z - min.z);
+    let cell_size = CELL_SIZE.max((area / MAX_CELLS).sqrt());
+    let navmesh = NavMesh::new(agent, cell_size, min - Vec3::Y, max + Vec3::Y * agent.height);
+    let cells = navmesh.cells_in(Vec2::new(min.x, min.z), Vec2::new(max.x, max.z)).collect();
+    commands.insert_resource(NavMeshBake { navmesh, cells, baked: 0, walkable: 0 });
+}
+
+/// Bakes the next slice of cells, and puts the navmesh to use once they're all done
+fn continue_navmesh_bake(
+    mut commands: Commands,
+    mut bake: ResMut<NavMeshBake>,
+    colliders: Query<(&ColliderOf, Has<Breakable>)>,
+    bodies: Query<&RigidBody>,
+    spatial_query: SpatialQuery,
+) {
+    let bake = &mut *bake;
+    let shape = agent_shape(&bake.navmesh.agent);
+    let is_geometry = |entity: Entity| is_level_geometry(entity, &colliders, &bodies);
+    let end = (bake.baked + BAKE_CELLS_PER_FRAME).min(bake.cells.len());
+    for &cell in &bake.cells[bake.baked..end] {
+        let height = bake_cell(&bake.navmesh, cell, &shape, &spatial_query, &is_geometry);
+        bake.walkable += height.is_some() as usize;
+        bake.navmesh.set_height(cell, height);
+    }
+    bake.baked = end;
+
+    if bake.baked < bake.cells.len() {
+        return;
+    }
+    let size = bake.navmesh.size();
+    info!("Baked navmesh: {}x{} cells of {:.2}m, {} walkable", size.x, size.y, bake.navmesh.cell_size, bake.walkable);
+    commands.insert_resource(bake.navmesh.clone());
+    commands.remove_resource::<NavMeshBake>();
+}
+
+/// Remembers the area covered by a prop as it's destroyed
+fn queue_rebake_on_break(
+    trigger: Trigger<OnRemove, Breakable>,
+    aabbs: Query<&ColliderAabb>,
+    mut queue: ResMut<NavMeshRebakeQueue>,
+) {
+    let Ok(aabb) = aabbs.get(trigger.target()) else { return };
+    queue.0.push(PendingRebake {
+        min: Vec2::new(aabb.min.x, aabb.min.z),
+        max: Vec2::new(aabb.max.x, aabb.max.z),
+        timer: Timer::from_seconds(REBAKE_DELAY_SECS, TimerMode::Once),
+    });
+}
+
+/// Rebakes the cells around destroyed props so paths can go through where they stood
+fn rebake_dirty_regions(
+    time: Res<Time>,
+    mut queue: ResMut<NavMeshRebakeQueue>,
+    mut navmesh: ResMut<NavMesh>,
+    colliders: Query<(&ColliderOf, Has<Breakable>)>,
+    bodies: Query<&RigidBody>,
+    spatial_query: SpatialQuery,
+) {
+    for pending in &mut queue.0 {
+        pending.timer.tick(time.delta());
+    }
+
+    let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut queue.0)
+        .into_iter()
+        .partition(|pending| pending.timer.finished());
+    queue.0 = waiting;
+
+    if ready.is_empty() {
+        return;
+    }
+
+    let shape = agent_shape(&navmesh.agent);
+    let is_geometry = |entity: Entity| is_level_geometry(entity, &colliders, &bodies);
+    for region in ready {
+        // The prop also blocked cells within an agent radius of it
+        let margin = Vec2::splat(navmesh.agent.radius + navmesh.cell_size);
+        let cells: Vec<UVec2> = navmesh.cells_in(region.min - margin, region.max + margin).collect();
+        for cell in cells {
+            let height = bake_cell(&navmesh, cell, &shape, &spatial_query, &is_geometry);
+            navmesh.set_height(cell, height);
+        }
+    }
+}
+
+/// Forgets the navmesh when the world goes away, so the next one is baked from its own level
+fn clear_navmesh(mut commands: Commands, mut rebake_queue: ResMut<NavMeshRebakeQueue>) {
+    commands.remove_resource::<NavMesh>();
+    commands.remove_resource::<NavMeshBake>();
+    rebake_queue.0.clear();
+}
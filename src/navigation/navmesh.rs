@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use bevy::prelude::*;
+
+// How many cells around an off-mesh position are searched for a walkable one
+const SNAP_SEARCH_RADIUS: i32 = 4;
+
+/// Size and movement limits of the characters that walk the navmesh
+#[derive(Debug, Clone, Copy)]
+pub struct NavAgent {
+    pub radius: f32,
+    pub height: f32,
+    pub max_slope: f32,   // Radians
+    pub step_height: f32, // Largest ledge the agent can walk up
+}
+
+/// Walkable area of the level, stored as a grid of cells with their ground height.
+/// Each column keeps only the topmost walkable surface.
+#[derive(Resource, Debug, Clone)]
+pub struct NavMesh {
+    pub agent: NavAgent,
+    pub cell_size: f32,
+    pub floor: f32,   // Lowest height the level geometry reaches
+    pub ceiling: f32, // Highest height the level geometry reaches
+    origin: Vec2, // World x/z of the corner of cell (0, 0)
+    width: usize,
+    depth: usize,
+    cells: Vec<Option<f32>>, // Ground height of each walkable cell
+}
+
+impl NavMesh {
+    /// An empty navmesh covering the `min..max` box of level geometry
+    pub fn new(agent: NavAgent, cell_size: f32, min: Vec3, max: Vec3) -> Self {
+        let width = ((max.x - min.x) / cell_size).ceil().max(1.0) as usize;
+        let depth = ((max.z - min.z) / cell_size).ceil().max(1.0) as usize;
+        Self {
+            agent,
+            cell_size,
+            floor: min.y,
+            ceiling: max.y,
+            origin: Vec2::new(min.x, min.z),
+            width,
+            depth,
+            cells: vec![None; width * depth],
+        }
+    }
+
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.width as u32, self.depth as u32)
+    }
+
+    /// The cell containing a world position, if it's inside the navmesh
+    pub fn cell_at(&self, position: Vec3) -> Option<UVec2> {
+        let local = (Vec2::new(position.x, position.z) - self.origin) / self.cell_size;
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+        let cell = UVec2::new(local.x as u32, local.y as u32);
+        self.in_bounds(cell.as_ivec2()).then_some(cell)
+    }
+
+    /// World x/z of the center of a cell
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    /// Ground height of a walkable cell, None if the cell is blocked
+    pub fn height(&self, cell: UVec2) -> Option<f32> {
+        self.cells[self.index(cell)]
+    }
+
+    pub fn set_height(&mut self, cell: UVec2, height: Option<f32>) {
+        let index = self.index(cell);
+        self.cells[index] = height;
+    }
+
+    pub fn is_walkable(&self, position: Vec3) -> bool {
+        self.cell_at(position).is_some_and(|cell| self.height(cell).is_some())
+    }
+
+    /// All cells overlapping a world x/z rectangle
+    pub fn cells_in(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = UVec2> {
+        let to_cell = |point: Vec2| ((point - self.origin) / self.cell_size).floor().as_ivec2();
+        let size = self.size().as_ivec2() - IVec2::ONE;
+        let start = to_cell(min).clamp(IVec2::ZERO, size);
+        let end = to_cell(max).clamp(IVec2::ZERO, size);
+        (start.y..=end.y).flat_map(move |z| (start.x..=end.x).map(move |x| UVec2::new(x as u32, z as u32)))
+    }
+
+    /// Shortest walkable path between two world positions, smoothed so that
+    /// waypoints are only placed where the path has to turn.
+    /// The first waypoint is the start cell, the last one is the goal.
+    pub fn find_path(&self, start: Vec3, goal: Vec3) -> Option<Vec<Vec3>> {
+        let start_cell = self.nearest_walkable(start)?;
+        let goal_cell = self.nearest_walkable(goal)?;
+
+        let cells = self.a_star(start_cell, goal_cell)?;
+        let mut path: Vec<Vec3> = cells.into_iter().map(|cell| self.cell_point(cell)).collect();
+
+        // End exactly on the goal if it's on the navmesh
+        if self.cell_at(goal) == Some(goal_cell) {
+            if let Some(last) = path.last_mut() {
+                *last = Vec3::new(goal.x, last.y, goal.z);
+            }
+        }
+
+        Some(self.smooth(path))
+    }
+
+    fn index(&self, cell: UVec2) -> usize {
+        cell.y as usize * self.width + cell.x as usize
+    }
+
+    fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && (cell.x as usize) < self.width && (cell.y as usize) < self.depth
+    }
+
+    /// Center of a walkable cell at ground height
+    fn cell_point(&self, cell: UVec2) -> Vec3 {
+        let center = self.cell_center(cell);
+        Vec3::new(center.x, self.height(cell).unwrap_or_default(), center.y)
+    }
+
+    /// The walkable cell closest to a position, searching a few cells around it
+    fn nearest_walkable(&self, position: Vec3) -> Option<UVec2> {
+        let local = ((Vec2::new(position.x, position.z) - self.origin) / self.cell_size).floor().as_ivec2();
+
+        let mut best: Option<(UVec2, i32)> = None;
+        for dz in -SNAP_SEARCH_RADIUS..=SNAP_SEARCH_RADIUS {
+            for dx in -SNAP_SEARCH_RADIUS..=SNAP_SEARCH_RADIUS {
+                let cell = local + IVec2::new(dx, dz);
+                if !self.in_bounds(cell) || self.height(cell.as_uvec2()).is_none() {
+                    continue;
+                }
+                let distance = dx * dx + dz * dz;
+                if best.is_none_or(|(_, best_distance)| distance < best_distance) {
+                    best = Some((cell.as_uvec2(), distance));
+                }
+            }
+        }
+        best.map(|(cell, _)| cell)
+    }
+
+    /// Whether the agent can move directly between two neighbouring walkable cells
+    fn can_step(&self, from: UVec2, to: UVec2) -> bool {
+        match (self.height(from), self.height(to)) {
+            (Some(from_height), Some(to_height)) => (to_height - from_height).abs() <= self.agent.step_height,
+            _ => false,
+        }
+    }
+
+    fn a_star(&self, start: UVec2, goal: UVec2) -> Option<Vec<UVec2>> {
+        const NEIGHBOURS: [IVec2; 8] = [
+            IVec2::new(1, 0), IVec2::new(-1, 0), IVec2::new(0, 1), IVec2::new(0, -1),
+            IVec2::new(1, 1), IVec2::new(1, -1), IVec2::new(-1, 1), IVec2::new(-1, -1),
+        ];
+
+        // Octile distance, admissible for 8-way movement
+        let heuristic = |cell: UVec2| {
+            let d = (cell.as_ivec2() - goal.as_ivec2()).abs().as_vec2();
+            d.x.max(d.y) + (std::f32::consts::SQRT_2 - 1.0) * d.x.min(d.y)
+        };
+
+        let mut cost = vec![f32::INFINITY; self.cells.len()];
+        let mut came_from: Vec<Option<UVec2>> = vec![None; self.cells.len()];
+        let mut open = BinaryHeap::new();
+
+        cost[self.index(start)] = 0.0;
+        open.push(OpenNode { cell: start, estimate: heuristic(start) });
+
+        while let Some(OpenNode { cell, estimate }) = open.pop() {
+            if cell == goal {
+                let mut path = vec![goal];
+                let mut current = goal;
+                while let Some(previous) = came_from[self.index(current)] {
+                    path.push(previous);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let current_cost = cost[self.index(cell)];
+            // Stale entry, this cell was already reached more cheaply
+            if estimate > current_cost + heuristic(cell) {
+                continue;
+            }
+
+            for offset in NEIGHBOURS {
+                let next = cell.as_ivec2() + offset;
+                if !self.in_bounds(next) {
+                    continue;
+                }
+                let next = next.as_uvec2();
+                if !self.can_step(cell, next) {
+                    continue;
+                }
+
+                // Don't cut corners past blocked cells on diagonals
+                let diagonal = offset.x != 0 && offset.y != 0;
+                if diagonal {
+                    let side_a = UVec2::new(next.x, cell.y);
+                    let side_b = UVec2::new(cell.x, next.y);
+                    if !self.can_step(cell, side_a) || !self.can_step(cell, side_b) {
+                        continue;
+                    }
+                }
+
+                let step_cost = if diagonal { std::f32::consts::SQRT_2 } else { 1.0 };
+                let next_cost = current_cost + step_cost;
+                let next_index = self.index(next);
+                if next_cost < cost[next_index] {
+                    cost[next_index] = next_cost;
+                    came_from[next_index] = Some(cell);
+                    open.push(OpenNode { cell: next, estimate: next_cost + heuristic(next) });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Removes waypoints that can be skipped by walking in a straight line (string pulling)
+    fn smooth(&self, path: Vec<Vec3>) -> Vec<Vec3> {
+        if path.len() <= 2 {
+            return path;
+        }
+
+        let mut smoothed = vec![path[0]];
+        let mut anchor = 0;
+        while anchor < path.len() - 1 {
+            // Furthest waypoint still reachable in a straight line from the anchor
+            let mut furthest = anchor + 1;
+            for candidate in (anchor + 2..path.len()).rev() {
+                if self.is_straight_walkable(path[anchor], path[candidate]) {
+                    furthest = candidate;
+                    break;
+                }
+            }
+            smoothed.push(path[furthest]);
+            anchor = furthest;
+        }
+        smoothed
+    }
+
+    /// Samples the segment between two points every half cell, checking every step is walkable
+    fn is_straight_walkable(&self, from: Vec3, to: Vec3) -> bool {
+        let flat = Vec2::new(to.x - from.x, to.z - from.z);
+        let steps = (flat.length() / (self.cell_size * 0.5)).ceil().max(1.0) as usize;
+
+        let mut previous = match self.cell_at(from) {
+            Some(cell) => cell,
+            None => return false,
+        };
+        for step in 1..=steps {
+            let point = from.lerp(to, step as f32 / steps as f32);
+            let Some(cell) = self.cell_at(point) else { return false };
+            if cell != previous {
+                if !self.can_step(previous, cell) {
+                    return false;
+                }
+                // Crossing diagonally must not clip a blocked corner either
+                if cell.x != previous.x && cell.y != previous.y
+                    && (!self.can_step(previous, UVec2::new(cell.x, previous.y))
+                        || !self.can_step(previous, UVec2::new(previous.x, cell.y))) {
+                    return false;
+                }
+                previous = cell;
+            }
+        }
+        true
+    }
+}
+
+/// Entry in the A* open set, ordered so the lowest estimate is popped first
+struct OpenNode {
+    cell: UVec2,
+    estimate: f32,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
@@ -0,0 +1,288 @@
+use avian3d::prelude::SpatialQuery;
+use bevy::prelude::*;
+use crate::animation::Footstep;
+use crate::character_controller::{Character, ControllerSet, Grounded};
+use crate::combat::{DamageEvent, Health};
+use crate::game_states::AppState;
+use crate::physics::has_line_of_sight;
+use crate::player::Player;
+
+pub struct PerceptionPlugin;
+
+impl Plugin for PerceptionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NoiseEvent>()
+            .init_resource::<PerceptionDebug>()
+            .add_systems(FixedUpdate, update_perception.in_set(ControllerSet::Intents))
+            .add_systems(FixedUpdate, emit_movement_noise
+                .after(ControllerSet::Simulate)
+                .run_if(in_state(AppState::InGame)))
+            .add_systems(Update, (
+                toggle_perception_debug,
+                debug_visualize_perception.run_if(|debug: Res<PerceptionDebug>| debug.0),
+            ).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+// How far the noises made by characters carry
+const SPRINT_NOISE_RADIUS: f32 = 8.0;
+const LANDING_NOISE_RADIUS: f32 = 6.0;
+
+// Height above a target's origin that sight rays aim at
+const TARGET_FOCUS_HEIGHT: f32 = 0.2;
+
+/// A sound that NPCs within `radius` can hear, louder the closer they are
+#[derive(Event, Debug, Clone, Copy)]
+pub struct NoiseEvent {
+    pub position: Vec3,
+    pub radius: f32,
+    pub source: Option<Entity>,
+}
+
+/// What an NPC can see and hear, and how aware it is of the player
+#[derive(Component, Debug)]
+pub struct Perception {
+    pub view_angle: f32,          // Half-angle of the sight cone, in radians
+    pub view_distance: f32,
+    pub eye_height: f32,          // Height above the origin that sight rays start from
+    pub hearing: f32,             // Multiplier on how far away noises can be heard
+    pub sight_gain: f32,          // Awareness gained per second with the target in view
+    pub decay_rate: f32,          // Awareness lost per second when nothing is sensed
+    pub suspicion_threshold: f32, // Awareness needed to turn toward what was sensed
+    pub aggro_threshold: f32,     // Awareness needed to engage
+
+    pub awareness: f32,           // 0 is oblivious, 1 is fully aware
+    pub target: Option<Entity>,
+    pub can_see_target: bool,
+    pub last_known_position: Option<Vec3>,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            view_angle: 60f32.to_radians(),
+            view_distance: 12.0,
+            eye_height: 0.3,
+            hearing: 1.0,
+            sight_gain: 1.5,       // Under a second to notice someone up close
+            decay_rate: 0.1,       // Fully forgets about ten seconds after losing track
+            suspicion_threshold: 0.3,
+            aggro_threshold: 0.7,
+
+            awareness: 0.0,
+            target: None,
+            can_see_target: false,
+            last_known_position: None,
+        }
+    }
+}
+
+impl Perception {
+    pub fn is_aggroed(&self) -> bool {
+        self.awareness >= self.aggro_threshold
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        self.awareness >= self.suspicion_threshold
+    }
+
+    /// Drops all awareness, e.g. when giving up a chase
+    pub fn forget(&mut self) {
+        self.awareness = 0.0;
+        self.target = None;
+        self.can_see_target = false;
+        self.last_known_position = None;
+    }
+}
+
+/// Toggles the perception gizmos
+#[derive(Resource, Default)]
+pub struct PerceptionDebug(pub bool);
+
+/// Updates awareness from sight, noises and being hit
+pub(crate) fn update_perception(
+    time: Res<Time>,
+    mut noise_events: EventReader<NoiseEvent>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut perceivers: Query<(Entity, &GlobalTransform, &mut Perception)>,
+    targets: Query<(Entity, &GlobalTransform, &Health), With<Player>>,
+    transforms: Query<&GlobalTransform>,
+    spatial_query: SpatialQuery,
+) {
+    let delta = time.delta_secs();
+    let noises: Vec<NoiseEvent> = noise_events.read().copied().collect();
+
+    for (entity, transform, mut perception) in &mut perceivers {
+        let eye = transform.translation() + Vec3::Y * perception.eye_height;
+        let forward = transform.rotation() * Vec3::Z; // The character model faces +Z
+        let forward = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+        let mut sensed = false;
+
+        // Sight: in the cone, in range and not hidden behind level geometry
+        perception.can_see_target = false;
+        for (target, target_transform, health) in &targets {
+            if health.is_dead() {
+                continue;
+            }
+
+            let target_point = target_transform.translation() + Vec3::Y * TARGET_FOCUS_HEIGHT;
+            let to_target = target_point - eye;
+            let distance = to_target.length();
+            if distance > perception.view_distance {
+                continue;
+            }
+
+            let flat_direction = Vec3::new(to_target.x, 0.0, to_target.z).normalize_or_zero();
+            if flat_direction != Vec3::ZERO && forward.angle_between(flat_direction) > perception.view_angle {
+                continue;
+            }
+
+            if !has_line_of_sight(&spatial_query, eye, target_point, entity, target) {
+                continue;
+            }
+
+            // Closer targets are noticed faster
+            let closeness = 1.0 - 0.5 * distance / perception.view_distance;
+            perception.awareness += perception.sight_gain * closeness * delta;
+            perception.target = Some(target);
+            perception.can_see_target = true;
+            perception.last_known_position = Some(target_transform.translation());
+            sensed = true;
+            break;
+        }
+
+        // Hearing: a noise raises awareness to how loud it was, it doesn't stack
+        for noise in &noises {
+            if noise.source == Some(entity) {
+                continue;
+            }
+
+            let radius = noise.radius * perception.hearing;
+            let distance = noise.position.distance(transform.translation());
+            if radius <= 0.0 || distance > radius {
+                continue;
+            }
+
+            let loudness = 1.0 - distance / radius;
+            if loudness > perception.awareness {
+                perception.awareness = loudness;
+                // Only look toward it if we aren't already watching the target
+                if !perception.can_see_target {
+                    perception.last_known_position = Some(noise.position);
+                }
+            }
+            sensed = true;
+        }
+
+        if !sensed {
+            perception.awareness -= perception.decay_rate * delta;
+        }
+        perception.awareness = perception.awareness.clamp(0.0, 1.0);
+
+        if perception.awareness <= 0.0 {
+            perception.forget();
+        }
+    }
+
+    // Getting hit gives away where the attacker is
+    for event in damage_events.read() {
+        let Some(source) = event.source else { continue };
+        let Ok((_, _, mut perception)) = perceivers.get_mut(event.target) else { continue };
+        perception.awareness = 1.0;
+        perception.target = Some(source);
+        perception.last_known_position = transforms.get(source).ok().map(|transform| transform.translation());
+    }
+}
+
+/// Sprinting and landing make noise
+fn emit_movement_noise(
+    mut noise_events: EventWriter<NoiseEvent>,
+    mut footsteps: EventReader<Footstep>,
+    characters: Query<(Entity, &Character, &GlobalTransform)>,
+    landed: Query<(Entity, &GlobalTransform), (With<Character>, Added<Grounded>)>,
+) {
+    for (entity, character, transform) in &characters {
+        // Animated characters are heard by their footfalls instead, below
+        if character.is_sprinting && character.is_moving && !character.animation_timing {
+            noise_events.write(NoiseEvent {
+                position: transform.translation(),
+                radius: SPRINT_NOISE_RADIUS,
+                source: Some(entity),
+            });
+        }
+    }
+
+    for footstep in footsteps.read() {
+        let Ok((_, character, _)) = characters.get(footstep.character) else {
+            continue;
+        };
+        if character.is_sprinting {
+            noise_events.write(NoiseEvent {
+                position: footstep.position,
+                radius: SPRINT_NOISE_RADIUS,
+                source: Some(footstep.character),
+            });
+        }
+    }
+
+    for (entity, transform) in &landed {
+        noise_events.write(NoiseEvent {
+            position: transform.translation(),
+            radius: LANDING_NOISE_RADIUS,
+            source: Some(entity),
+        });
+    }
+}
+
+fn toggle_perception_debug(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut debug: ResMut<PerceptionDebug>,
+) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        debug.0 = !debug.0;
+    }
+}
+
+fn debug_visualize_perception(
+    mut gizmos: Gizmos,
+    query: Query<(&Perception, &GlobalTransform)>,
+) {
+    const ARC_SEGMENTS: usize = 16;
+
+    for (perception, transform) in &query {
+        let eye = transform.translation() + Vec3::Y * perception.eye_height;
+        let forward = transform.rotation() * Vec3::Z;
+        let forward = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+
+        // Green when oblivious, through yellow, to red when aggroed
+        let color = if perception.is_aggroed() {
+            Color::srgb(1.0, 0.0, 0.0)
+        } else {
+            Color::srgb(perception.awareness / perception.aggro_threshold, 1.0, 0.0)
+        };
+
+        // Draw the sight cone as two edges and the arc at the far end
+        let arc: Vec<Vec3> = (0..=ARC_SEGMENTS)
+            .map(|segment| {
+                let t = segment as f32 / ARC_SEGMENTS as f32;
+                let angle = -perception.view_angle + 2.0 * perception.view_angle * t;
+                eye + Quat::from_rotation_y(angle) * forward * perception.view_distance
+            })
+            .collect();
+        gizmos.line(eye, arc[0], color);
+        gizmos.line(eye, arc[ARC_SEGMENTS], color);
+        gizmos.linestrip(arc, color);
+
+        // Draw awareness as a bar above the head
+        let right = Vec3::Y.cross(forward).normalize_or_zero();
+        let bar_start = eye + Vec3::Y * 0.4 - right * 0.5;
+        gizmos.line(bar_start, bar_start + right, Color::srgb(0.2, 0.2, 0.2));
+        gizmos.line(bar_start, bar_start + right * perception.awareness, color);
+
+        // Mark where the NPC thinks the player is
+        if let Some(position) = perception.last_known_position {
+            gizmos.sphere(position, 0.2, color);
+            gizmos.line(eye, position, color.with_alpha(0.3));
+        }
+    }
+}
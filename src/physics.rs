@@ -1,5 +1,5 @@
 use avian3d::PhysicsPlugins;
-use avian3d::prelude::{PhysicsDebugPlugin, PhysicsInterpolationPlugin};
+use avian3d::prelude::{PhysicsDebugPlugin, PhysicsInterpolationPlugin, PhysicsLayer, SpatialQuery, SpatialQueryFilter};
 use bevy::prelude::*;
 use crate::breakable::BreakablePropsPlugin;
 
@@ -14,3 +14,30 @@ impl Plugin for PhysicsPlugin {
     }
 }
 
+/// Collision layers. Anything without `CollisionLayers` is on `Default` and collides with everything.
+#[derive(PhysicsLayer, Default)]
+pub enum GameLayer {
+    #[default]
+    Default,
+    Ragdoll, // Parts of a ragdoll, which pass through each other but not the world
+}
+
+/// Raycasts from the viewer to the target, ignoring the viewer itself
+pub fn has_line_of_sight(
+    spatial_query: &SpatialQuery,
+    from: Vec3,
+    to: Vec3,
+    viewer: Entity,
+    target: Entity,
+) -> bool {
+    let offset = to - from;
+    let Ok(direction) = Dir3::new(offset) else {
+        return true;
+    };
+
+    let filter = SpatialQueryFilter::default().with_excluded_entities([viewer]);
+    match spatial_query.cast_ray(from, direction, offset.length(), true, &filter) {
+        Some(hit) => hit.entity == target,
+        None => true,
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::bonfire::{Bonfire, RespawnPoint, RestEvent};
+use crate::breakable::{Breakable, DestroyedProps};
+use crate::character_controller::Character;
+use crate::combat::Health;
+use crate::config::{self, RonFileError};
+use crate::game_states::{AppState, InWorld};
+use crate::inventory::{Inventory, ItemStack};
+use crate::player::Player;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveSaveSlot>()
+            .add_event::<SaveGameEvent>()
+            .add_systems(Update, apply_pending_load
+                .run_if(resource_exists::<PendingLoad>)
+                .run_if(in_state(AppState::InGame)))
+            // After a rest has reset the world, so the save matches what the player comes back to
+            .add_systems(PostUpdate, autosave
+                .run_if(on_event::<RestEvent>.or(on_event::<SaveGameEvent>))
+                .run_if(in_state(InWorld)));
+    }
+}
+
+/// Current version of the save format, bump it when [`SaveData`] changes
+pub const SAVE_VERSION: u32 = 1;
+
+pub const SAVE_SLOTS: usize = 3;
+
+const SAVE_DIRECTORY: &str = "saves";
+
+/// Everything written to a save slot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub saved_at: u64, // Seconds since the Unix epoch
+    pub player: PlayerSave,
+    pub respawn: RespawnSave,
+    pub destroyed_props: Vec<String>,
+    pub opened_shortcuts: Vec<String>, // Always empty until the world has shortcuts
+    pub inventory: Vec<ItemStack>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    pub health: f32,
+    pub max_health: f32,
+    pub stamina: f32,
+    pub max_stamina: f32,
+    pub equip_load: f32,
+    pub max_equip_load: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RespawnSave {
+    pub position: [f32; 3],
+    pub bonfire: Option<String>, // Name of the bonfire last rested at
+}
+
+/// Only the version, read first to decide how to parse the rest of the file
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    File(RonFileError),
+    TooNew { version: u32 },
+    TooOld { version: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::File(RonFileError::Parse(error)) => write!(f, "the save file is damaged: {error}"),
+            SaveError::File(error) => write!(f, "{error}"),
+            SaveError::TooNew { version } => write!(
+                f, "saved by a newer version of the game (format v{version}, this version reads up to v{SAVE_VERSION})"
+            ),
+            SaveError::TooOld { version } => write!(f, "format v{version} is too old to be loaded"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<RonFileError> for SaveError {
+    fn from(error: RonFileError) -> Self {
+        SaveError::File(error)
+    }
+}
+
+/// Asks for the game to be written to the active slot, e.g. when quitting. Props broken since the
+/// last rest are only kept by these saves, resting puts them all back.
+#[derive(Event, Debug)]
+pub struct SaveGameEvent;
+
+/// What's in a save slot
+#[derive(Debug, Clone)]
+pub enum SaveSlot {
+    Empty,
+    Saved(SaveData),
+    Unreadable(String), // Why it can't be loaded
+}
+
+/// The slot autosaves are written to, picked when starting or loading a game
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct ActiveSaveSlot(pub usize);
+
+/// A save to restore once the world has been spawned
+#[derive(Resource, Debug, Clone)]
+pub struct PendingLoad(pub SaveData);
+
+fn slot_path(slot: usize) -> PathBuf {
+    PathBuf::from(SAVE_DIRECTORY).join(format!("slot_{}.ron", slot + 1))
+}
+
+/// Parses a save, upgrading older formats to the current one
+fn parse_save(text: &str) -> Result<SaveData, SaveError> {
+    let header: SaveHeader = ron::from_str(text).map_err(RonFileError::from)?;
+    match header.version {
+        SAVE_VERSION => Ok(ron::from_str(text).map_err(RonFileError::from)?),
+        // Migrations from older versions go here, each upgrading to the next version
+        version if version > SAVE_VERSION => Err(SaveError::TooNew { version }),
+        version => Err(SaveError::TooOld { version }),
+    }
+}
+
+pub fn read_slot(slot: usize) -> Result<Option<SaveData>, SaveError> {
+    // Read as text first, the version decides how the rest is parsed
+    let Some(text) = config::read_text(slot_path(slot))? else {
+        return Ok(None);
+    };
+    parse_save(&text).map(Some)
+}
+
+/// Reads every slot, keeping the reason for any that can't be loaded
+pub fn read_slots() -> Vec<SaveSlot> {
+    (0..SAVE_SLOTS)
+        .map(|slot| match read_slot(slot) {
+            Ok(Some(data)) => SaveSlot::Saved(data),
+            Ok(None) => SaveSlot::Empty,
+            Err(error) => {
+                warn!("Save slot {} can't be loaded: {error}", slot + 1);
+                SaveSlot::Unreadable(error.to_string())
+            }
+        })
+        .collect()
+}
+
+pub fn write_slot(slot: usize, data: &SaveData) -> Result<(), SaveError> {
+    Ok(config::save_ron(slot_path(slot), data)?)
+}
+
+/// Gathers the state of the game into a save
+fn capture(
+    (transform, character, health, inventory): (&Transform, &Character, &Health, &Inventory),
+    respawn_point: &RespawnPoint,
+    bonfire_name: Option<&Name>,
+    destroyed_props: &DestroyedProps,
+) -> SaveData {
+    let mut destroyed_props: Vec<String> = destroyed_props.0.iter().cloned().collect();
+    destroyed_props.sort();
+
+    SaveData {
+        version: SAVE_VERSION,
+        saved_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        player: PlayerSave {
+            position: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+            health: health.current,
+            max_health: health.max,
+            stamina: character.stamina,
+            max_stamina: character.max_stamina,
+            equip_load: character.equip_load,
+            max_equip_load: character.max_equip_load,
+        },
+        respawn: RespawnSave {
+            position: respawn_point.position.to_array(),
+            bonfire: bonfire_name.map(|name| name.to_string()),
+        },
+        destroyed_props,
+        opened_shortcuts: Vec::new(),
+        inventory: inventory.items.clone(),
+    }
+}
+
+fn autosave(
+    slot: Res<ActiveSaveSlot>,
+    players: Query<(&Transform, &Character, &Health, &Inventory), With<Player>>,
+    respawn_point: Res<RespawnPoint>,
+    bonfires: Query<&Name, With<Bonfire>>,
+    destroyed_props: Res<DestroyedProps>,
+) {
+    let Ok(player) = players.single() else { return };
+    let bonfire_name = respawn_point.bonfire.and_then(|bonfire| bonfires.get(bonfire).ok());
+    let data = capture(player, &respawn_point, bonfire_name, &destroyed_props);
+
+    match write_slot(slot.0, &data) {
+        Ok(()) => info!("Saved to slot {}", slot.0 + 1),
+        Err(error) => error!("Couldn't save to slot {}: {error}", slot.0 + 1),
+    }
+}
+
+/// Puts the freshly spawned world into the state of the save being loaded
+fn apply_pending_load(
+    mut commands: Commands,
+    pending: Res<PendingLoad>,
+    mut players: Query<(&mut Transform, &mut Character, &mut Health, &mut Inventory), With<Player>>,
+    props: Query<(Entity, &Name), With<Breakable>>,
+    bonfires: Query<(Entity, &Name), With<Bonfire>>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut destroyed_props: ResMut<DestroyedProps>,
+) {
+    // Wait for the player to be spawned
+    let Ok((mut transform, mut character, mut health, mut inventory)) = players.single_mut() else { return };
+    let data = &pending.0;
+
+    transform.translation = Vec3::from_array(data.player.position);
+    transform.rotation = Quat::from_array(data.player.rotation).normalize();
+    health.max = data.player.max_health;
+    health.current = data.player.health.min(health.max);
+    character.max_stamina = data.player.max_stamina;
+    character.stamina = data.player.stamina.min(character.max_stamina);
+    character.equip_load = data.player.equip_load;
+    character.max_equip_load = data.player.max_equip_load;
+    inventory.items = data.inventory.clone();
+
+    let bonfire = data.respawn.bonfire.as_ref().and_then(|saved_name| {
+        bonfires.iter().find(|(_, name)| name.as_str() == saved_name).map(|(entity, _)| entity)
+    });
+    *respawn_point = RespawnPoint {
+        position: Vec3::from_array(data.respawn.position),
+        bonfire,
+    };
+
+    destroyed_props.0 = data.destroyed_props.iter().cloned().collect();
+    for (entity, name) in &props {
+        if destroyed_props.0.contains(name.as_str()) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    commands.remove_resource::<PendingLoad>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save_data() -> SaveData {
+        SaveData {
+            version: SAVE_VERSION,
+            saved_at: 0,
+            player: PlayerSave {
+                position: [1.0, 2.0, 3.0],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                health: 80.0,
+                max_health: 100.0,
+                stamina: 50.0,
+                max_stamina: 100.0,
+                equip_load: 10.0,
+                max_equip_load: 60.0,
+            },
+            respawn: RespawnSave {
+                position: [0.0; 3],
+                bonfire: Some("Bonfire".to_string()),
+            },
+            destroyed_props: vec!["Vase".to_string()],
+            opened_shortcuts: Vec::new(),
+            inventory: vec![ItemStack { id: "flask".to_string(), count: 3 }],
+        }
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let text = ron::ser::to_string(&save_data()).unwrap();
+        let parsed = parse_save(&text).unwrap();
+        assert_eq!(parsed.player.position, [1.0, 2.0, 3.0]);
+        assert_eq!(parsed.destroyed_props, vec!["Vase".to_string()]);
+        assert_eq!(parsed.inventory, save_data().inventory);
+    }
+
+    #[test]
+    fn newer_version_is_rejected_before_the_rest_is_parsed() {
+        // Fields this version doesn't know about can't get in the way
+        let text = format!("(version: {}, new_field: true)", SAVE_VERSION + 1);
+        assert!(matches!(parse_save(&text), Err(SaveError::TooNew { version }) if version == SAVE_VERSION + 1));
+    }
+
+    #[test]
+    fn older_version_is_rejected() {
+        assert!(matches!(parse_save("(version: 0)"), Err(SaveError::TooOld { version: 0 })));
+    }
+
+    #[test]
+    fn damaged_file_is_corrupt() {
+        assert!(matches!(parse_save("(version: "), Err(SaveError::File(RonFileError::Parse(_)))));
+        assert!(matches!(parse_save("(player: ())"), Err(SaveError::File(RonFileError::Parse(_)))));
+    }
+}
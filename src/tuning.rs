@@ -0,0 +1,190 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::character_controller::{
+    Character, InputBuffer, JumpImpulse, MaxSlopeAngle, MovementDampingFactor,
+    DEFAULT_BUSY_BUFFER_WINDOW, DEFAULT_INPUT_BUFFER_WINDOW, DEFAULT_JUMP_BUFFER_WINDOW, DEFAULT_MAX_SLOPE_DEGREES,
+};
+use crate::config::RonFileError;
+use crate::player::Player;
+
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<PlayerTuning>()
+            .init_asset_loader::<PlayerTuningLoader>()
+            .add_systems(Startup, load_player_tuning)
+            .add_systems(Update, apply_player_tuning);
+    }
+}
+
+const PLAYER_TUNING_PATH: &str = "tuning/player.tuning.ron";
+
+/// Movement values for the player, read from `assets/tuning/player.tuning.ron`.
+/// Fields missing from the file keep the built-in defaults.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PlayerTuning {
+    // Movement
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub movement_damping: f32,
+    pub max_slope_degrees: f32,
+
+    // Jumping
+    pub jump_impulse: f32,
+    pub fall_multiplier: f32,
+    pub low_jump_multiplier: f32,
+    pub coyote_time: f32,
+
+    // Rolling
+    pub roll_speed: f32,
+    pub roll_duration: f32,
+    pub roll_cooldown: f32,
+    pub roll_recovery: f32,
+
+    // Stamina
+    pub stamina_regen_rate: f32,
+    pub stamina_use_rate: f32,
+    pub roll_stamina_cost: f32,
+    pub light_attack_stamina_cost: f32,
+    pub heavy_attack_stamina_cost: f32,
+
+    // Input
+    pub input_buffer_window: f32,
+    pub jump_buffer_window: f32,
+    pub busy_buffer_window: f32,
+}
+
+impl Default for PlayerTuning {
+    fn default() -> Self {
+        let character = Character::default();
+        Self {
+            walk_speed: character.walk_speed,
+            run_speed: character.run_speed,
+            movement_damping: 0.9,
+            max_slope_degrees: DEFAULT_MAX_SLOPE_DEGREES,
+
+            jump_impulse: 7.0,
+            fall_multiplier: character.fall_multiplier,
+            low_jump_multiplier: character.low_jump_multiplier,
+            coyote_time: character.coyote_time,
+
+            roll_speed: character.roll_speed,
+            roll_duration: character.roll_duration,
+            roll_cooldown: character.roll_cooldown,
+            roll_recovery: character.roll_recovery,
+
+            stamina_regen_rate: character.stamina_regen_rate,
+            stamina_use_rate: character.stamina_use_rate,
+            roll_stamina_cost: character.roll_stamina_cost,
+            light_attack_stamina_cost: character.light_attack_stamina_cost,
+            heavy_attack_stamina_cost: character.heavy_attack_stamina_cost,
+
+            input_buffer_window: DEFAULT_INPUT_BUFFER_WINDOW,
+            jump_buffer_window: DEFAULT_JUMP_BUFFER_WINDOW,
+            busy_buffer_window: DEFAULT_BUSY_BUFFER_WINDOW,
+        }
+    }
+}
+
+impl PlayerTuning {
+    fn apply(
+        &self,
+        character: &mut Character,
+        input_buffer: &mut InputBuffer,
+        jump_impulse: &mut JumpImpulse,
+        max_slope: &mut MaxSlopeAngle,
+        damping: &mut MovementDampingFactor,
+    ) {
+        character.walk_speed = self.walk_speed;
+        character.run_speed = self.run_speed;
+        damping.0 = self.movement_damping;
+        max_slope.0 = self.max_slope_degrees.to_radians();
+
+        jump_impulse.0 = self.jump_impulse;
+        character.fall_multiplier = self.fall_multiplier;
+        character.low_jump_multiplier = self.low_jump_multiplier;
+        character.coyote_time = self.coyote_time;
+
+        character.roll_speed = self.roll_speed;
+        character.roll_duration = self.roll_duration;
+        character.roll_cooldown = self.roll_cooldown;
+        character.roll_recovery = self.roll_recovery;
+
+        character.stamina_regen_rate = self.stamina_regen_rate;
+        character.stamina_use_rate = self.stamina_use_rate;
+        character.roll_stamina_cost = self.roll_stamina_cost;
+        character.light_attack_stamina_cost = self.light_attack_stamina_cost;
+        character.heavy_attack_stamina_cost = self.heavy_attack_stamina_cost;
+
+        input_buffer.window = self.input_buffer_window;
+        input_buffer.jump_window = self.jump_buffer_window;
+        input_buffer.busy_window = self.busy_buffer_window;
+    }
+}
+
+#[derive(Default)]
+struct PlayerTuningLoader;
+
+impl AssetLoader for PlayerTuningLoader {
+    type Asset = PlayerTuning;
+    type Settings = ();
+    type Error = RonFileError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tuning.ron"]
+    }
+}
+
+/// Keeps the tuning asset loaded so edits to the file are picked up
+#[derive(Resource)]
+struct PlayerTuningHandle(Handle<PlayerTuning>);
+
+fn load_player_tuning(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PlayerTuningHandle(asset_server.load(PLAYER_TUNING_PATH)));
+}
+
+/// Applies the tuning to a newly spawned player, and to the live player whenever the file changes
+fn apply_player_tuning(
+    mut asset_events: EventReader<AssetEvent<PlayerTuning>>,
+    handle: Res<PlayerTuningHandle>,
+    tunings: Res<Assets<PlayerTuning>>,
+    mut players: Query<(
+        Ref<Player>,
+        &mut Character,
+        &mut InputBuffer,
+        &mut JumpImpulse,
+        &mut MaxSlopeAngle,
+        &mut MovementDampingFactor,
+    )>,
+) {
+    let id = handle.0.id();
+    let changed = asset_events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(id) || event.is_modified(id));
+
+    let Some(tuning) = tunings.get(id) else { return };
+    for (player, mut character, mut input_buffer, mut jump_impulse, mut max_slope, mut damping) in &mut players {
+        if changed || player.is_added() {
+            tuning.apply(&mut character, &mut input_buffer, &mut jump_impulse, &mut max_slope, &mut damping);
+        }
+    }
+
+    if changed {
+        info!("Applied player tuning from {PLAYER_TUNING_PATH}");
+    }
+}
@@ -4,20 +4,19 @@ use avian3d::prelude::{RigidBody};
 use bevy::pbr::CascadeShadowConfigBuilder;
 use bevy::pbr::light_consts::lux;
 use bevy::prelude::*;
-use crate::game_states::AppState;
+use crate::game_states::{AppState, InWorld};
 
 pub(crate) struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(OnEnter(AppState::InGame), setup)
+            .add_systems(OnEnter(InWorld), setup)
             .add_systems(Update, dynamic_scene.run_if(in_state(AppState::InGame)))
         ;
     }
 }
 
-
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -28,11 +27,13 @@ fn setup(
         Transform::from_rotation(Quat::from_rotation_y(-PI * 0.5)),
         ColliderConstructorHierarchy::new(ColliderConstructor::ConvexHullFromMesh),
         RigidBody::Static,
+        StateScoped(InWorld),
     ));
 
     commands.spawn((
         SceneRoot(asset_server.load("models/piggy.glb#Scene0")),
         Transform::from_xyz(20.0, -0.0, 20.0).with_scale(Vec3::new(0.3, 0.3, 0.3)).with_rotation(Quat::from_rotation_y(-PI * 0.25)),
+        StateScoped(InWorld),
     ));
 
 
@@ -42,6 +43,7 @@ fn setup(
         Transform::from_xyz(0.0, 0.0, 0.0),
         ColliderConstructorHierarchy::new(ColliderConstructor::TrimeshFromMesh),
         RigidBody::Static,
+        StateScoped(InWorld),
     ));
 
 
@@ -59,6 +61,7 @@ fn setup(
             ..default()
         }
             .build(),
+        StateScoped(InWorld),
     ));
 }
 fn dynamic_scene(mut suns: Query<&mut Transform, With<DirectionalLight>>, time: Res<Time>) {